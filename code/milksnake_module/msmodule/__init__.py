@@ -1,20 +1,154 @@
+import datetime
+import json
+
 from msmodule._native import ffi, lib
 
+
+def _check_native():
+    """Check that the loaded library matches the declarations in _native
+
+    The library describes its exports in a JSON manifest; calling a function
+    whose signature differs from the one cffi was built with would crash.
+    """
+    try:
+        version = lib.msmodule_abi_version()
+        manifest = json.loads(ffi.string(lib.msmodule_manifest()).decode("utf-8"))
+    except AttributeError as e:
+        raise ImportError("msmodule._native is too old to describe its ABI: %s" % e)
+
+    if version != lib.MSMODULE_ABI_VERSION:
+        raise ImportError("msmodule._native has ABI version %d, expected %d"
+                          % (version, lib.MSMODULE_ABI_VERSION))
+
+    functions = {func["name"]: func for func in manifest["functions"]}
+    for name in dir(lib):
+        try:
+            declared = getattr(lib, name)
+        except AttributeError:
+            raise ImportError("msmodule._native does not export %s" % name)
+        if isinstance(declared, int):
+            continue
+
+        func = functions.get(name)
+        if func is None:
+            raise ImportError("msmodule._native does not describe %s" % name)
+
+        declared = ffi.typeof(declared)
+        params = [ffi.typeof(param["type"]) for param in func["params"]]
+        if params != list(declared.args) or ffi.typeof(func["returns"]) != declared.result:
+            raise ImportError("msmodule._native exports %s with a different signature"
+                              % name)
+
+
+_check_native()
+
+
+class PanicError(RuntimeError):
+    """Raised when the Rust library panics"""
+
+
+_EXCEPTIONS = {
+    lib.MS_STATUS_OVERFLOW: OverflowError,
+    lib.MS_STATUS_NULL_POINTER: ValueError,
+    lib.MS_STATUS_PANIC: PanicError,
+    lib.MS_STATUS_BUFFER_TOO_SMALL: ValueError,
+    lib.MS_STATUS_INVALID_ARGUMENT: ValueError,
+    lib.MS_STATUS_UNSUPPORTED: NotImplementedError,
+}
+
+
+def _check(status):
+    """Raise the exception corresponding to a non-OK status code"""
+    if status == lib.MS_STATUS_OK:
+        return
+
+    message = lib.msmodule_last_error_message()
+    if message == ffi.NULL:
+        message = "msmodule call failed with status %d" % status
+    else:
+        message = ffi.string(message).decode("utf-8", "replace")
+    lib.msmodule_clear_error()
+
+    raise _EXCEPTIONS.get(status, RuntimeError)(message)
+
+
 def pascal_row(n):
     l = ffi.new("size_t *")
-
-    # Get a C array of length l
-    arr = lib.pascal_row(n, l)
+    _check(lib.pascal_row_len(n, l))
     size = l[0]
 
-    try:
-        out = [arr[i] for i in range(size)]
-    finally:
-        lib.deallocate_vec(arr, size)
+    # The array is owned (and freed) by cffi, so nothing can leak
+    arr = ffi.new("uint32_t[]", size)
+    _check(lib.pascal_row_into(n, arr, size))
+
+    return list(arr)
+
+
+def outstanding_allocations():
+    """(address, length) of each array from lib.pascal_row not yet freed
+
+    This needs a library built with the track-allocations feature, and raises
+    NotImplementedError otherwise.
+
+    Only callers of lib.pascal_row itself show up here: the pascal_row wrapper
+    in this module copies rows into arrays owned by cffi instead.
+    """
+    count = ffi.new("size_t *")
+    _check(lib.msmodule_outstanding_allocations(ffi.NULL, 0, count))
+
+    # Arrays may be allocated or freed between the two calls
+    out = ffi.new("MsAllocation[]", count[0])
+    _check(lib.msmodule_outstanding_allocations(out, len(out), count))
+
+    return [(int(ffi.cast("uintptr_t", a.ptr)), a.len)
+            for a in out[0:min(len(out), count[0])]]
+
+
+def binomial(n, k):
+    out = ffi.new("uint32_t *")
+
+    _check(lib.binomial(n, k, out))
+
+    return out[0]
+
+
+def pascal_rows():
+    """Iterate over the rows of Pascal's triangle that fit in uint32"""
+    gen = ffi.gc(lib.msmodule_generator_new(), lib.msmodule_generator_free)
+
+    n = 1
+    while True:
+        arr = ffi.new("uint32_t[]", n)
+        status = lib.msmodule_generator_next(gen, arr, n)
+        if status == lib.MS_STATUS_OVERFLOW:
+            lib.msmodule_clear_error()
+            return
+        _check(status)
+
+        yield list(arr)
+        n += 1
+
+
+def to_timestamp(d):
+    """Unix timestamp of midnight UTC at the start of the date ``d``"""
+    out = ffi.new("int64_t *")
+    _check(lib.msmodule_date_to_timestamp((d.year, d.month, d.day), out))
+
+    return out[0]
 
-    return out
 
+def seconds_before(d, seconds):
+    """The UTC date ``seconds`` seconds before midnight at the start of ``d``"""
+    out = ffi.new("MsDate *")
+    _check(lib.msmodule_date_seconds_before((d.year, d.month, d.day), seconds, out))
 
+    return datetime.date(out.year, out.month, out.day)
 
 
+class Point:
+    def __init__(self, x, y):
+        self.x = x
+        self.y = y
 
+    def norm(self):
+        return lib.msmodule_point_norm((self.x, self.y))
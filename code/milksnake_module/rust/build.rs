@@ -1,12 +1,347 @@
 extern crate cbindgen;
 
 use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// Directory to write the header to instead of `OUT_DIR`
+///
+/// Relative paths are resolved against the crate root.
+const HEADER_DIR_VAR: &str = "MSMODULE_HEADER_DIR";
+
+const HEADER_NAME: &str = "msmodule.h";
+const CDEF_NAME: &str = "msmodule.cdef.h";
+const INCLUDE_GUARD: &str = "MSMODULE_H";
+const MANIFEST_NAME: &str = "manifest.json";
+
+/// Element type and ownership rules of each export, for the manifest
+///
+/// The types themselves are taken from the generated declarations. Every
+/// `#[no_mangle]` function needs an entry here, or the build fails.
+const OWNERSHIP: &[(&str, Option<&str>, &str)] = &[
+    (
+        "pascal_row",
+        Some("uint32_t"),
+        "the library allocates *out; free it with deallocate_vec(*out, *size_out)",
+    ),
+    ("pascal_row_len", None, "nothing is allocated"),
+    ("pascal_row_into", Some("uint32_t"), "the caller owns out"),
+    (
+        "pascal_rows_foreach",
+        Some("uint32_t"),
+        "each row is borrowed by the callback for the duration of the call",
+    ),
+    ("deallocate_vec", Some("uint32_t"), "frees an array allocated by pascal_row"),
+    (
+        "msmodule_outstanding_allocations",
+        Some("MsAllocation"),
+        "the caller owns out; the arrays listed still have to be freed with deallocate_vec",
+    ),
+    ("binomial", Some("uint32_t"), "the caller owns out"),
+    (
+        "msmodule_last_error_message",
+        Some("char"),
+        "the library owns the string; it is valid until the next failed call or \
+         msmodule_clear_error on the same thread",
+    ),
+    ("msmodule_clear_error", None, "frees the last error message of this thread"),
+    ("msmodule_abi_version", None, "nothing is allocated"),
+    ("msmodule_manifest", Some("char"), "the string is static; never free it"),
+    (
+        "msmodule_generator_new",
+        None,
+        "the library allocates the generator; free it with msmodule_generator_free",
+    ),
+    ("msmodule_generator_next", Some("uint32_t"), "the caller owns out"),
+    ("msmodule_generator_reset", None, "nothing is allocated"),
+    ("msmodule_generator_free", None, "frees a generator allocated by msmodule_generator_new"),
+    ("msmodule_date_to_timestamp", None, "the caller owns out"),
+    ("msmodule_date_seconds_before", None, "the caller owns out"),
+    ("msmodule_point_norm", None, "nothing is allocated"),
+];
+
+fn env_var(name: &str) -> Result<String, String> {
+    env::var(name).map_err(|err| format!("{} is not set: {}", name, err))
+}
+
+fn output_dir() -> Result<PathBuf, String> {
+    match env::var_os(HEADER_DIR_VAR) {
+        Some(dir) => Ok(PathBuf::from(env_var("CARGO_MANIFEST_DIR")?).join(dir)),
+        None => Ok(PathBuf::from(env_var("OUT_DIR")?)),
+    }
+}
+
+/// Write `contents` to `path` unless it already holds exactly that
+///
+/// This keeps the mtime of the outputs stable across builds that do not change
+/// them.
+fn write_if_changed(path: &Path, contents: &str) -> Result<(), String> {
+    if fs::read_to_string(path).ok().as_deref() == Some(contents) {
+        return Ok(());
+    }
+
+    fs::write(path, contents).map_err(|err| format!("cannot write {}: {}", path.display(), err))
+}
+
+/// Add the version macros and the C++ `extern "C"` block to cbindgen's output
+///
+/// cbindgen 0.5 only wraps declarations in `extern "C"` when generating C++,
+/// so the block is spliced in between the includes and the end of the include
+/// guard.
+fn finish_header(header: &str) -> Result<String, String> {
+    let body_start = header
+        .rfind("#include <")
+        .and_then(|i| header[i..].find('\n').map(|j| i + j + 1))
+        .ok_or("cbindgen output has no #include lines")?;
+    let guard_end = header
+        .rfind(&format!("#endif /* {} */", INCLUDE_GUARD))
+        .ok_or("cbindgen output has no include guard")?;
+
+    let mut out = String::with_capacity(header.len() + 512);
+    out.push_str(&header[..body_start]);
+    out.push_str(&format!(
+        "\n#define MSMODULE_VERSION \"{}\"\n\
+         #define MSMODULE_VERSION_MAJOR {}\n\
+         #define MSMODULE_VERSION_MINOR {}\n\
+         #define MSMODULE_VERSION_PATCH {}\n",
+        env_var("CARGO_PKG_VERSION")?,
+        env_var("CARGO_PKG_VERSION_MAJOR")?,
+        env_var("CARGO_PKG_VERSION_MINOR")?,
+        env_var("CARGO_PKG_VERSION_PATCH")?,
+    ));
+    out.push_str("\n#ifdef __cplusplus\nextern \"C\" {\n#endif\n");
+    out.push_str(&header[body_start..guard_end]);
+    out.push_str("#ifdef __cplusplus\n} /* extern \"C\" */\n#endif\n\n");
+    out.push_str(&header[guard_end..]);
+
+    Ok(out)
+}
+
+/// Strip cbindgen's output down to something cffi's `cdef()` can parse
+///
+/// Preprocessor lines are dropped, except that integer `#define`s become
+/// anonymous enums so that their values stay available from Python. Comments
+/// are kept, since cffi ignores them.
+fn cdef(header: &str) -> String {
+    let mut out = String::with_capacity(header.len());
+    for line in header.lines() {
+        if !line.starts_with('#') {
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        }
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if let ["#define", name, value] = words[..] {
+            if value.parse::<i64>().is_ok() {
+                out.push_str(&format!("enum {{ {} = {} }};\n", name, value));
+            }
+        }
+    }
+
+    out
+}
+
+/// Names of the `#[no_mangle]` functions defined in `source`
+fn exports(source: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut lines = source.lines();
+    while let Some(line) = lines.next() {
+        if line.trim() != "#[no_mangle]" {
+            continue;
+        }
+
+        let name = lines
+            .by_ref()
+            .filter_map(|line| line.split("fn ").nth(1))
+            .next()
+            .and_then(|rest| rest.split('(').next());
+        if let Some(name) = name {
+            names.push(name.trim().to_string());
+        }
+    }
+
+    names
+}
+
+/// Find the declaration of `name` in the generated file `file`
+///
+/// Declarations are the lines that start with a type, so that mentions of a
+/// function in another one's documentation do not count. Each must follow a
+/// doc comment. Returns the declaration, joined onto one line if cbindgen
+/// wrapped it.
+fn declaration(file: &str, text: &str, name: &str) -> Result<String, String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let start = lines.iter().position(|line| {
+        !line.starts_with(' ')
+            && !line.starts_with('/')
+            && (line.contains(&format!(" {}(", name)) || line.contains(&format!("*{}(", name)))
+    });
+
+    match start {
+        None => Err(format!("export `{}` is missing from {}", name, file)),
+        Some(i) if i == 0 || lines[i - 1].trim() != "*/" => Err(format!(
+            "export `{}` has no doc comment in {}; document it with `///`",
+            name, file
+        )),
+        Some(i) => {
+            let mut decl = String::new();
+            for line in &lines[i..] {
+                if !decl.is_empty() && !decl.ends_with('(') {
+                    decl.push(' ');
+                }
+                decl.push_str(line.trim());
+                if line.ends_with(';') {
+                    break;
+                }
+            }
+            Ok(decl)
+        }
+    }
+}
+
+/// Split `uint32_t **out` into `("uint32_t **", "out")`
+fn split_type_name(decl: &str) -> (&str, &str) {
+    let decl = decl.trim();
+    let i = decl.rfind([' ', '*']).map_or(0, |i| i + 1);
+    (decl[..i].trim(), &decl[i..])
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Build the JSON manifest returned by `msmodule_manifest`
+///
+/// `declarations` holds the C declaration of each export, as found in the
+/// generated header.
+fn manifest(abi_version: &str, declarations: &[String]) -> Result<String, String> {
+    let mut functions = Vec::new();
+    for decl in declarations {
+        let (head, rest) = decl.split_at(decl.find('(').ok_or("declaration without (")?);
+        let params = &rest[1..rest.rfind(')').ok_or("declaration without )")?];
+        let (returns, name) = split_type_name(head);
+
+        let &(_, element_type, ownership) = OWNERSHIP
+            .iter()
+            .find(|entry| entry.0 == name)
+            .ok_or_else(|| format!("export `{}` has no ownership entry in build.rs", name))?;
+
+        let params: Vec<String> = params
+            .split(',')
+            .filter(|param| param.trim() != "void")
+            .map(|param| {
+                let (ty, name) = split_type_name(param);
+                format!("{{\"name\": {}, \"type\": {}}}", json_string(name), json_string(ty))
+            })
+            .collect();
+
+        functions.push(format!(
+            "    {{\n      \"name\": {},\n      \"returns\": {},\n      \"params\": [{}],\n      \
+             \"element_type\": {},\n      \"ownership\": {}\n    }}",
+            json_string(name),
+            json_string(returns),
+            params.join(", "),
+            element_type.map_or("null".to_string(), json_string),
+            json_string(ownership),
+        ));
+    }
+
+    Ok(format!(
+        "{{\n  \"abi_version\": {},\n  \"functions\": [\n{}\n  ]\n}}\n",
+        abi_version,
+        functions.join(",\n")
+    ))
+}
+
+/// Value of the integer `#define name` in cbindgen's output
+fn define(header: &str, name: &str) -> Result<String, String> {
+    let prefix = format!("#define {} ", name);
+    header
+        .lines()
+        .find(|line| line.starts_with(&prefix))
+        .map(|line| line[prefix.len()..].trim().to_string())
+        .ok_or_else(|| format!("{} is not defined in {}", name, HEADER_NAME))
+}
+
+fn run() -> Result<(), String> {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-env-changed={}", HEADER_DIR_VAR);
+
+    let crate_dir = env_var("CARGO_MANIFEST_DIR")?;
+    let config = cbindgen::Config {
+        language: cbindgen::Language::C,
+        include_guard: Some(INCLUDE_GUARD.to_string()),
+        autogen_warning: Some(
+            "/* Generated from the Rust sources by build.rs; do not edit by hand. */".to_string(),
+        ),
+        export: cbindgen::ExportConfig {
+            // cbindgen emits every `const` in the crate, private or not
+            exclude: vec!["SECONDS_PER_DAY".to_string(), "UNIX_EPOCH_OFFSET".to_string()],
+            rename: [
+                ("MAX_ROW_LEN", "MSMODULE_MAX_ROW_LEN"),
+                ("MIN_YEAR", "MSMODULE_MIN_YEAR"),
+                ("MAX_YEAR", "MSMODULE_MAX_YEAR"),
+            ]
+            .iter()
+            .map(|&(from, to)| (from.to_string(), to.to_string()))
+            .collect(),
+            ..Default::default()
+        },
+        enumeration: cbindgen::EnumConfig {
+            // MsStatus::Ok becomes MS_STATUS_OK
+            rename_variants: Some(cbindgen::RenameRule::QualifiedScreamingSnakeCase),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let bindings = cbindgen::generate_with_config(&crate_dir, config)
+        .map_err(|err| format!("cbindgen failed to generate {}: {}", HEADER_NAME, err))?;
+    let mut generated = Vec::new();
+    bindings.write(&mut generated);
+    let generated = String::from_utf8(generated)
+        .map_err(|err| format!("cbindgen produced invalid UTF-8: {}", err))?;
+    let header = finish_header(&generated)?;
+    let cdef = cdef(&generated);
+
+    let lib_path = Path::new(&crate_dir).join("src/lib.rs");
+    let lib_source = fs::read_to_string(&lib_path)
+        .map_err(|err| format!("cannot read {}: {}", lib_path.display(), err))?;
+    let mut declarations = Vec::new();
+    for name in exports(&lib_source) {
+        declarations.push(declaration(HEADER_NAME, &header, &name)?);
+        declaration(CDEF_NAME, &cdef, &name)?;
+    }
+    let manifest = manifest(&define(&generated, "MSMODULE_ABI_VERSION")?, &declarations)?;
+
+    let dir = output_dir()?;
+    fs::create_dir_all(&dir).map_err(|err| format!("cannot create {}: {}", dir.display(), err))?;
+    write_if_changed(&dir.join(HEADER_NAME), &header)?;
+    write_if_changed(&dir.join(CDEF_NAME), &cdef)?;
+    // The manifest is compiled into the library, so it always goes to OUT_DIR
+    write_if_changed(&Path::new(&env_var("OUT_DIR")?).join(MANIFEST_NAME), &manifest)?;
+
+    println!("cargo:rustc-env=MSMODULE_HEADER={}", dir.join(HEADER_NAME).display());
+    println!("cargo:rustc-env=MSMODULE_CDEF={}", dir.join(CDEF_NAME).display());
+    Ok(())
+}
 
 fn main() {
-    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
-    let mut config: cbindgen::Config = Default::default();
-    config.language = cbindgen::Language::C;
-    cbindgen::generate_with_config(&crate_dir, config)
-        .unwrap()
-        .write_to_file("target/msmodule.h");
+    if let Err(err) = run() {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
 }
@@ -0,0 +1,100 @@
+//! Proleptic Gregorian calendar arithmetic for `MsDate`
+//!
+//! Conversions between dates and day counts use Howard Hinnant's
+//! `days_from_civil` and `civil_from_days` algorithms, which work in 400-year
+//! eras and so need no lookup tables.
+
+/// Smallest and largest years accepted, matching Python's `datetime.date`
+pub const MIN_YEAR: i32 = 1;
+pub const MAX_YEAR: i32 = 9999;
+
+const SECONDS_PER_DAY: i64 = 86400;
+
+/// Days from 0000-03-01 to 1970-01-01
+const UNIX_EPOCH_OFFSET: i64 = 719_468;
+
+/// A calendar date
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MsDate {
+    /// Year, from 1 to 9999
+    pub year: i32,
+    /// Month, from 1 to 12
+    pub month: u8,
+    /// Day of the month, from 1 to the length of the month
+    pub day: u8,
+}
+
+fn is_leap(year: i32) -> bool {
+    (year % 4 == 0) && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i32, month: u8) -> u8 {
+    match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 of the date `year`-`month`-`day`
+fn days_from_civil(year: i32, month: u8, day: u8) -> i64 {
+    // Count years from March, so that the leap day is the last of the year
+    let year = i64::from(year) - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (i64::from(month) + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - UNIX_EPOCH_OFFSET
+}
+
+/// Inverse of `days_from_civil`, returning `(year, month, day)`
+fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let days = days + UNIX_EPOCH_OFFSET;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month as u8, day as u8)
+}
+
+impl MsDate {
+    pub fn is_valid(&self) -> bool {
+        (MIN_YEAR..=MAX_YEAR).contains(&self.year)
+            && (1..=12).contains(&self.month)
+            && self.day >= 1
+            && self.day <= days_in_month(self.year, self.month)
+    }
+
+    /// Seconds from the Unix epoch to midnight UTC at the start of this date
+    pub fn to_timestamp(self) -> i64 {
+        days_from_civil(self.year, self.month, self.day) * SECONDS_PER_DAY
+    }
+
+    /// The UTC date containing `timestamp`, if it is in the supported range
+    pub fn from_timestamp(timestamp: i64) -> Option<MsDate> {
+        let (year, month, day) = civil_from_days(timestamp.div_euclid(SECONDS_PER_DAY));
+        if year < i64::from(MIN_YEAR) || year > i64::from(MAX_YEAR) {
+            return None;
+        }
+
+        Some(MsDate {
+            year: year as i32,
+            month,
+            day,
+        })
+    }
+}
@@ -0,0 +1,108 @@
+//! Error reporting across the C ABI
+//!
+//! Every fallible export returns an `MsStatus` and records a human-readable
+//! message in thread-local storage, which C callers retrieve with
+//! `msmodule_last_error_message`. Panics are caught before they can unwind
+//! into C and are reported the same way. Allocation failures abort the process
+//! rather than panicking, so they cannot be reported.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::panic::{self, UnwindSafe};
+use std::ptr;
+
+/// Status code returned by every fallible export
+// This is a plain C enum rather than a fixed-width one: cbindgen declares
+// fixed-width enums as a tag plus a typedef of the same name, which C++
+// rejects.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MsStatus {
+    /// The call succeeded
+    Ok = 0,
+    /// The result does not fit in the output type
+    Overflow = 1,
+    /// A required pointer argument was null
+    NullPointer = 2,
+    /// The Rust code panicked
+    Panic = 3,
+    /// A caller-provided buffer is too short for the result
+    BufferTooSmall = 4,
+    /// An argument is outside the range the function accepts
+    InvalidArgument = 5,
+    /// The library was built without the feature this function needs
+    Unsupported = 6,
+}
+
+/// A failed call: the status to return and the message to record
+#[derive(Debug)]
+pub struct Error {
+    status: MsStatus,
+    message: String,
+}
+
+impl Error {
+    pub fn new<S: Into<String>>(status: MsStatus, message: S) -> Error {
+        Error {
+            status,
+            message: message.into(),
+        }
+    }
+
+    /// Error for a null `name` argument
+    pub fn null_pointer(name: &str) -> Error {
+        Error::new(MsStatus::NullPointer, format!("{} must not be null", name))
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: &str) {
+    // A C string cannot contain interior NULs
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Run `f` with the closure's result written back to C as a status code
+pub fn guard<F>(f: F) -> MsStatus
+where
+    F: FnOnce() -> Result<(), Error> + UnwindSafe,
+{
+    match panic::catch_unwind(f) {
+        Ok(Ok(())) => MsStatus::Ok,
+        Ok(Err(err)) => {
+            set_last_error(&err.message);
+            err.status
+        }
+        Err(payload) => {
+            set_last_error(&format!("panic in msmodule: {}", panic_message(&*payload)));
+            MsStatus::Panic
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic payload"
+    }
+}
+
+/// Pointer to the message of the last failed call on this thread, or null
+pub fn last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| match *last.borrow() {
+        Some(ref message) => message.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+pub fn clear_error() {
+    LAST_ERROR.with(|last| *last.borrow_mut() = None);
+}
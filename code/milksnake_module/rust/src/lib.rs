@@ -1,37 +1,545 @@
+mod date;
+mod error;
+mod tracking;
+
+use std::cmp;
+use std::convert::TryFrom;
 use std::mem;
-use std::os::raw::c_ulonglong;
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+
+use error::{guard, Error};
+pub use date::MsDate;
+pub use error::MsStatus;
+
+/// Version of the ABI described by `msmodule.h`
+///
+/// This is bumped whenever an export is removed or its signature or ownership
+/// rules change.
+pub const MSMODULE_ABI_VERSION: u32 = 1;
+
+/// C's `size_t`, used for every length and count in the ABI
+///
+/// Rust's `usize` has the same size and alignment as `size_t` on every target
+/// Rust supports. cbindgen recognises the name and emits it as `size_t`.
+#[allow(non_camel_case_types)]
+pub type size_t = usize;
+
+// The generated header declares these with C types, so their layouts must
+// match on every target the crate is built for
+const _LAYOUT_CHECKS: () = {
+    assert!(mem::size_of::<size_t>() == mem::size_of::<*const c_void>());
+    assert!(mem::size_of::<MsStatus>() == mem::size_of::<i32>());
+    assert!(mem::size_of::<bool>() == 1);
+    assert!(mem::size_of::<PascalRowCallback>() == mem::size_of::<*const c_void>());
+    assert!(mem::size_of::<MsDate>() == 8 && mem::align_of::<MsDate>() == 4);
+    assert!(mem::size_of::<MsPoint>() == 8 && mem::align_of::<MsPoint>() == 4);
+    assert!(mem::size_of::<MsAllocation>() == 2 * mem::size_of::<*const c_void>());
+};
+
+/// Length of the longest row whose entries all fit in a `u32`
+///
+/// The next row contains C(35, 17) = 4537567650 > 2**32 - 1.
+const MAX_ROW_LEN: usize = 35;
+
+fn row_overflow(n: usize) -> Error {
+    Error::new(
+        MsStatus::Overflow,
+        format!("row {} of Pascal's triangle does not fit in uint32", n),
+    )
+}
+
+/// Replace row `i - 1` of the triangle (of length `i`) with row `i` in place
+///
+/// Returns `None`, with `row` unchanged, if the new row does not fit in a
+/// `u32`.
+fn next_row(row: &mut Vec<u32>) -> Option<()> {
+    if row.windows(2).any(|pair| pair[0].checked_add(pair[1]).is_none()) {
+        return None;
+    }
+
+    // row[j] needs the previous row's row[j - 1], which is only overwritten
+    // on the following iteration
+    for j in (1..row.len()).rev() {
+        row[j] += row[j - 1];
+    }
+    row.push(1);
+
+    Some(())
+}
+
+/// Compute the nth row of Pascal's triangle, which is empty for n == 0
+///
+/// Building the rows one after the other means that any `n` past 35 fails on
+/// row 36, without ever allocating more than 35 entries.
+fn pascal_row_impl(n: usize) -> Option<Vec<u32>> {
+    let mut row: Vec<u32> = Vec::new();
+    for _ in 0..n {
+        next_row(&mut row)?;
+    }
+
+    Some(row)
+}
+
+fn gcd(mut a: u32, mut b: u32) -> u32 {
+    while b != 0 {
+        let t = a % b;
+        a = b;
+        b = t;
+    }
+
+    a
+}
+
+fn binomial_impl(n: usize, k: usize) -> Option<u32> {
+    if k > n {
+        return Some(0);
+    }
+
+    let k = cmp::min(k, n - k);
+
+    // C(n, i + 1) = C(n, i) * (n - i) / (i + 1), dividing out common factors
+    // first so that no intermediate value is larger than the result
+    let mut result: u32 = 1;
+    for i in 0..k {
+        let num = u32::try_from(n - i).ok()?;
+        let den = u32::try_from(i + 1).ok()?;
+        let g = gcd(result, den);
+        result = (result / g).checked_mul(num / (den / g))?;
+    }
+
+    Some(result)
+}
 
-type size_t = c_ulonglong;
+/// Get the nth row of Pascal's triangle
+///
+/// On success, writes a pointer to the row to `*out` and its length to
+/// `*size_out`. Row 1 is `{1}` and row 0 is empty: `*size_out` is set to 0
+/// and `*out` is non-null but must not be dereferenced.
+///
+/// Returns `MsStatus::Overflow` if any entry of the row does not fit in a
+/// `uint32_t`, in which case `*out` is set to null and `*size_out` to 0.
+///
+/// # Safety
+///
+/// `out` and `size_out` must be valid pointers. The row must be freed with
+/// `deallocate_vec`, passing the length written to `size_out`.
+#[no_mangle]
+pub unsafe extern "C" fn pascal_row(n: size_t, out: *mut *mut u32, size_out: *mut size_t) -> MsStatus {
+    guard(|| {
+        if out.is_null() {
+            return Err(Error::null_pointer("out"));
+        }
+        if size_out.is_null() {
+            return Err(Error::null_pointer("size_out"));
+        }
+
+        *out = ptr::null_mut();
+        *size_out = 0;
 
-fn pascal_row_impl(n: usize) -> Vec<u32> {
-    let mut row: Vec<u32> = Vec::with_capacity(n);
-    row.resize(n, 0); // allocate an array of 0s
-    row[0] = 1;
+        // A boxed slice has no spare capacity, so deallocate_vec can rebuild
+        // the allocation from the length alone
+        let row = pascal_row_impl(n).ok_or_else(|| row_overflow(n))?.into_boxed_slice();
+        *size_out = row.len();
+        *out = Box::into_raw(row) as *mut u32; // freed by deallocate_vec
+        tracking::record(*out, *size_out);
+        Ok(())
+    })
+}
 
-    let mut last: u32;
-    for i in 1..n {
-        let mut curr: u32 = 1;
-        for j in 1..(i + 1) {
-            last = curr;
-            curr = row[j];
-            row[j] = last + curr;
+/// Get the number of entries `pascal_row_into` writes for row `n`
+///
+/// Writes the length to `*len_out`, or returns `MsStatus::Overflow` (leaving
+/// `*len_out` untouched) if the row does not fit in a `uint32_t` array.
+///
+/// # Safety
+///
+/// `len_out` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn pascal_row_len(n: size_t, len_out: *mut size_t) -> MsStatus {
+    guard(|| {
+        if len_out.is_null() {
+            return Err(Error::null_pointer("len_out"));
+        }
+        if n > MAX_ROW_LEN {
+            return Err(row_overflow(n));
+        }
+
+        *len_out = n;
+        Ok(())
+    })
+}
+
+/// Write the nth row of Pascal's triangle into a caller-provided buffer
+///
+/// `out` must have room for at least `pascal_row_len(n)` entries; only the
+/// first `n` are written. Unlike `pascal_row`, the caller keeps ownership of
+/// the memory, so there is nothing to free with `deallocate_vec`.
+///
+/// Returns `MsStatus::Overflow` if any entry of the row does not fit in a
+/// `uint32_t`, or `MsStatus::BufferTooSmall` if `out_len < n`. The buffer is
+/// left untouched on failure.
+///
+/// # Safety
+///
+/// `out` must point to at least `out_len` writable `uint32_t`s. It may be null
+/// if `n` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn pascal_row_into(n: size_t, out: *mut u32, out_len: size_t) -> MsStatus {
+    guard(|| {
+        if n > MAX_ROW_LEN {
+            return Err(row_overflow(n));
+        }
+        if out_len < n {
+            return Err(Error::new(
+                MsStatus::BufferTooSmall,
+                format!("row {} needs a buffer of {} entries, got {}", n, n, out_len),
+            ));
+        }
+        if n == 0 {
+            return Ok(());
+        }
+        if out.is_null() {
+            return Err(Error::null_pointer("out"));
         }
+
+        let row = pascal_row_impl(n).ok_or_else(|| row_overflow(n))?;
+        ptr::copy_nonoverlapping(row.as_ptr(), out, row.len());
+        Ok(())
+    })
+}
+
+/// Called by `pascal_rows_foreach` with each row in turn
+///
+/// `row` points to `len` entries and is only valid for the duration of the
+/// call. Return `true` to continue with the next row or `false` to stop.
+pub type PascalRowCallback = Option<extern "C" fn(row: *const u32, len: size_t, user_data: *mut c_void) -> bool>;
+
+/// Call `callback` with rows 1 to `n_max` of Pascal's triangle
+///
+/// A single row is updated in place between calls, so nothing is allocated
+/// per row. `user_data` is passed through to `callback` unchanged.
+///
+/// Stopping early from the callback is not an error. Returns
+/// `MsStatus::Overflow` once a row does not fit in a `uint32_t`, after
+/// `callback` has seen all the rows before it, and `MsStatus::NullPointer` if
+/// `callback` is null.
+///
+/// # Safety
+///
+/// `callback` must be null or a valid function pointer that is safe to call
+/// with `user_data`.
+#[no_mangle]
+pub unsafe extern "C" fn pascal_rows_foreach(
+    n_max: size_t,
+    callback: PascalRowCallback,
+    user_data: *mut c_void,
+) -> MsStatus {
+    guard(|| {
+        let callback = callback.ok_or_else(|| Error::null_pointer("callback"))?;
+
+        let mut row: Vec<u32> = Vec::new();
+        for n in 1..=n_max {
+            next_row(&mut row).ok_or_else(|| row_overflow(n))?;
+            if !callback(row.as_ptr(), row.len(), user_data) {
+                break;
+            }
+        }
+
+        Ok(())
+    })
+}
+
+/// Free an array returned by `pascal_row`
+///
+/// Passing a null `ptr` does nothing.
+///
+/// If the library was built with the `track-allocations` feature, `ptr` and
+/// `len` are checked against the arrays `pascal_row` has returned, and
+/// `MsStatus::InvalidArgument` is returned (without freeing anything) for
+/// double frees, wrong lengths and unknown pointers.
+///
+/// # Safety
+///
+/// `ptr` and `len` must come from the same successful call to `pascal_row`,
+/// and the array must not be freed twice.
+#[no_mangle]
+pub unsafe extern "C" fn deallocate_vec(ptr: *mut u32, len: size_t) -> MsStatus {
+    guard(|| {
+        if !ptr.is_null() {
+            tracking::release(ptr, len)?;
+            drop(Vec::from_raw_parts(ptr, len, len));
+        }
+        Ok(())
+    })
+}
+
+/// An array returned by `pascal_row` that has not been freed
+#[repr(C)]
+pub struct MsAllocation {
+    pub ptr: *const u32,
+    pub len: size_t,
+}
+
+/// List the arrays returned by `pascal_row` that have not been freed yet
+///
+/// Writes up to `out_len` of them to `out` and the total number outstanding to
+/// `*count_out`, so passing an `out_len` of 0 just counts them. Empty rows are
+/// not listed.
+///
+/// Returns `MsStatus::Unsupported` unless the library was built with the
+/// `track-allocations` feature.
+///
+/// # Safety
+///
+/// `count_out` must be a valid pointer, and `out` must point to at least
+/// `out_len` writable `MsAllocation`s. `out` may be null if `out_len` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn msmodule_outstanding_allocations(
+    out: *mut MsAllocation,
+    out_len: size_t,
+    count_out: *mut size_t,
+) -> MsStatus {
+    guard(|| {
+        if count_out.is_null() {
+            return Err(Error::null_pointer("count_out"));
+        }
+        if out.is_null() && out_len > 0 {
+            return Err(Error::null_pointer("out"));
+        }
+
+        let allocations = tracking::outstanding()?;
+        *count_out = allocations.len();
+        for (i, &(addr, len)) in allocations.iter().take(out_len).enumerate() {
+            *out.add(i) = MsAllocation {
+                ptr: addr as *const u32,
+                len,
+            };
+        }
+        Ok(())
+    })
+}
+
+/// Compute the binomial coefficient C(n, k) without building its row
+///
+/// Writes the result to `*out`, or returns `MsStatus::Overflow` (leaving
+/// `*out` untouched) if the result does not fit in a `uint32_t`. C(n, k) is 0
+/// for k > n.
+///
+/// # Safety
+///
+/// `out` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn binomial(n: size_t, k: size_t, out: *mut u32) -> MsStatus {
+    guard(|| {
+        if out.is_null() {
+            return Err(Error::null_pointer("out"));
+        }
+
+        *out = binomial_impl(n, k).ok_or_else(|| {
+            Error::new(MsStatus::Overflow, format!("C({}, {}) does not fit in uint32", n, k))
+        })?;
+        Ok(())
+    })
+}
+
+/// Get the error message of the last failed call on this thread
+///
+/// Returns null if no call has failed since the last `msmodule_clear_error`.
+/// The string is owned by the library and stays valid until the next failed
+/// call or `msmodule_clear_error` on the same thread.
+#[no_mangle]
+pub extern "C" fn msmodule_last_error_message() -> *const c_char {
+    error::last_error_message()
+}
+
+/// Forget the error message of the last failed call on this thread
+#[no_mangle]
+pub extern "C" fn msmodule_clear_error() {
+    error::clear_error()
+}
+
+/// Get the ABI version of the loaded library
+///
+/// Compare this with `MSMODULE_ABI_VERSION` from the header the caller was
+/// built against.
+#[no_mangle]
+pub extern "C" fn msmodule_abi_version() -> u32 {
+    MSMODULE_ABI_VERSION
+}
+
+/// Get a JSON description of every export of the loaded library
+///
+/// The manifest lists the name, return type and parameters of each function,
+/// the element type of any arrays it reads or writes and who frees what. The
+/// string is static and must not be freed.
+#[no_mangle]
+pub extern "C" fn msmodule_manifest() -> *const c_char {
+    // Generated by build.rs from the header; see OWNERSHIP there
+    const MANIFEST: &str = concat!(include_str!(concat!(env!("OUT_DIR"), "/manifest.json")), "\0");
+
+    MANIFEST.as_ptr() as *const c_char
+}
+
+/// Successive rows of Pascal's triangle, computed one from the next
+///
+/// This is opaque to C: create one with `msmodule_generator_new` and free it
+/// with `msmodule_generator_free`.
+pub struct PascalGenerator {
+    row: Vec<u32>,
+}
+
+/// Create a generator positioned before the first row of the triangle
+///
+/// The returned handle must be freed with `msmodule_generator_free`.
+#[no_mangle]
+pub extern "C" fn msmodule_generator_new() -> *mut PascalGenerator {
+    Box::into_raw(Box::new(PascalGenerator { row: Vec::new() }))
+}
+
+/// Write the next row of the triangle into `out`
+///
+/// The kth call after creating or resetting the generator writes row k, which
+/// has k entries, so `len` must be at least k.
+///
+/// Returns `MsStatus::BufferTooSmall` if `len` is too short, or
+/// `MsStatus::Overflow` if the row does not fit in a `uint32_t`. The generator
+/// does not advance on failure.
+///
+/// # Safety
+///
+/// `handle` must come from `msmodule_generator_new` and not have been freed,
+/// and `out` must point to at least `len` writable `uint32_t`s.
+#[no_mangle]
+pub unsafe extern "C" fn msmodule_generator_next(
+    handle: *mut PascalGenerator,
+    out: *mut u32,
+    len: size_t,
+) -> MsStatus {
+    guard(|| {
+        if handle.is_null() {
+            return Err(Error::null_pointer("handle"));
+        }
+        if out.is_null() {
+            return Err(Error::null_pointer("out"));
+        }
+
+        let row = &mut (*handle).row;
+        let n = row.len() + 1;
+        if len < n {
+            return Err(Error::new(
+                MsStatus::BufferTooSmall,
+                format!("row {} needs a buffer of {} entries, got {}", n, n, len),
+            ));
+        }
+
+        next_row(row).ok_or_else(|| row_overflow(n))?;
+        ptr::copy_nonoverlapping(row.as_ptr(), out, n);
+        Ok(())
+    })
+}
+
+/// Move the generator back before the first row of the triangle
+///
+/// # Safety
+///
+/// `handle` must come from `msmodule_generator_new` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn msmodule_generator_reset(handle: *mut PascalGenerator) -> MsStatus {
+    guard(|| {
+        if handle.is_null() {
+            return Err(Error::null_pointer("handle"));
+        }
+
+        (*handle).row.clear();
+        Ok(())
+    })
+}
+
+/// Free a generator created by `msmodule_generator_new`
+///
+/// Passing a null `handle` does nothing.
+///
+/// # Safety
+///
+/// `handle` must come from `msmodule_generator_new` and must not be freed
+/// twice.
+#[no_mangle]
+pub unsafe extern "C" fn msmodule_generator_free(handle: *mut PascalGenerator) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
     }
+}
+
+fn check_date(date: &MsDate) -> Result<(), Error> {
+    if date.is_valid() {
+        Ok(())
+    } else {
+        Err(Error::new(
+            MsStatus::InvalidArgument,
+            format!("invalid date: {:04}-{:02}-{:02}", date.year, date.month, date.day),
+        ))
+    }
+}
 
-    row
+/// Get the Unix timestamp of midnight UTC at the start of `date`
+///
+/// Returns `MsStatus::InvalidArgument` if `date` is not a valid date between
+/// years 1 and 9999.
+///
+/// # Safety
+///
+/// `out` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn msmodule_date_to_timestamp(date: MsDate, out: *mut i64) -> MsStatus {
+    guard(|| {
+        if out.is_null() {
+            return Err(Error::null_pointer("out"));
+        }
+        check_date(&date)?;
+
+        *out = date.to_timestamp();
+        Ok(())
+    })
 }
 
+/// Get the UTC date `seconds` seconds before midnight UTC at the start of `date`
+///
+/// Returns `MsStatus::InvalidArgument` if `date` is invalid, or
+/// `MsStatus::Overflow` if the result is outside years 1 to 9999.
+///
+/// # Safety
+///
+/// `out` must be a valid pointer.
 #[no_mangle]
-pub unsafe extern "C" fn pascal_row(n: usize, size_out: *mut size_t) -> *mut u32 {
-    let mut s = pascal_row_impl(n);
-    *size_out = s.len() as size_t;
-    let rv = s.as_mut_ptr();
-    mem::forget(s); // prevent rust from de-allocating this
-    rv
+pub unsafe extern "C" fn msmodule_date_seconds_before(
+    date: MsDate,
+    seconds: i64,
+    out: *mut MsDate,
+) -> MsStatus {
+    guard(|| {
+        if out.is_null() {
+            return Err(Error::null_pointer("out"));
+        }
+        check_date(&date)?;
+
+        let overflow = || Error::new(MsStatus::Overflow, "date value out of range");
+        let timestamp = date.to_timestamp().checked_sub(seconds).ok_or_else(overflow)?;
+        *out = MsDate::from_timestamp(timestamp).ok_or_else(overflow)?;
+        Ok(())
+    })
+}
+
+/// A point in the plane
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MsPoint {
+    pub x: i32,
+    pub y: i32,
 }
 
+/// Get the distance of `point` from the origin
 #[no_mangle]
-pub unsafe extern "C" fn deallocate_vec(ptr: *mut u32, len: size_t) {
-    let len = len as usize;
-    drop(Vec::from_raw_parts(ptr, len, len));
+pub extern "C" fn msmodule_point_norm(point: MsPoint) -> f64 {
+    f64::from(point.x).hypot(f64::from(point.y))
 }
@@ -0,0 +1,93 @@
+//! Registry of the arrays handed to C by `pascal_row`, for debugging wrappers
+//!
+//! With the `track-allocations` feature, `deallocate_vec` checks each pointer
+//! and length against the registry instead of trusting them, so double frees,
+//! wrong lengths and foreign pointers are reported rather than corrupting the
+//! heap. Without it, every function here does nothing.
+//!
+//! Empty rows own no memory and all share one dangling pointer, so they are
+//! not tracked. The addresses of freed arrays are kept, to tell double frees
+//! apart from unknown pointers, so the registry only ever grows.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ptr::NonNull;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use error::{Error, MsStatus};
+
+const ENABLED: bool = cfg!(feature = "track-allocations");
+
+struct Registry {
+    /// Length of each live array, by address
+    live: BTreeMap<usize, usize>,
+    freed: BTreeSet<usize>,
+}
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    live: BTreeMap::new(),
+    freed: BTreeSet::new(),
+});
+
+fn registry() -> MutexGuard<'static, Registry> {
+    // A panic while the lock was held cannot leave the maps inconsistent
+    REGISTRY.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Record an array of `len` entries at `ptr` handed to C
+pub fn record(ptr: *const u32, len: usize) {
+    if !ENABLED || len == 0 {
+        return;
+    }
+
+    let mut registry = registry();
+    registry.freed.remove(&(ptr as usize));
+    registry.live.insert(ptr as usize, len);
+}
+
+/// Check that C may free the array of `len` entries at `ptr`, and forget it
+pub fn release(ptr: *const u32, len: usize) -> Result<(), Error> {
+    // Any other pointer freed with length 0 is still checked, since it is
+    // either a live array with the wrong length or not an array at all
+    let empty = len == 0 && ptr == NonNull::<u32>::dangling().as_ptr();
+    if !ENABLED || empty {
+        return Ok(());
+    }
+
+    let mut registry = registry();
+    let addr = ptr as usize;
+    match registry.live.get(&addr).cloned() {
+        Some(expected) if expected == len => {
+            registry.live.remove(&addr);
+            registry.freed.insert(addr);
+            Ok(())
+        }
+        Some(expected) => Err(Error::new(
+            MsStatus::InvalidArgument,
+            format!("array at {:p} has length {}, not {}", ptr, expected, len),
+        )),
+        None if registry.freed.contains(&addr) => Err(Error::new(
+            MsStatus::InvalidArgument,
+            format!("double free of the array at {:p}", ptr),
+        )),
+        None => Err(Error::new(
+            MsStatus::InvalidArgument,
+            format!("{:p} was not returned by pascal_row", ptr),
+        )),
+    }
+}
+
+/// Address and length of every array that has not been freed yet
+pub fn outstanding() -> Result<Vec<(usize, usize)>, Error> {
+    if !ENABLED {
+        return Err(Error::new(
+            MsStatus::Unsupported,
+            "msmodule was built without the track-allocations feature",
+        ));
+    }
+
+    Ok(registry()
+        .live
+        .iter()
+        .map(|(&addr, &len)| (addr, len))
+        .collect())
+}
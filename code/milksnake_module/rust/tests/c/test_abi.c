@@ -0,0 +1,243 @@
+/*
+ * Calls every export of msmodule through the generated header
+ *
+ * Built and run by tests/c_abi.rs; exits with a non-zero status (and prints
+ * the failing check) if anything does not behave as documented.
+ */
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "msmodule.h"
+
+_Static_assert(sizeof(MsStatus) == 4, "MsStatus must be an int32_t");
+_Static_assert(sizeof(size_t) == sizeof(void *), "size_t must be pointer-sized");
+
+static int failures = 0;
+
+#define CHECK(cond)                                                          \
+    do {                                                                     \
+        if (!(cond)) {                                                       \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, \
+                    #cond);                                                  \
+            failures++;                                                      \
+        }                                                                    \
+    } while (0)
+
+static const uint32_t ROW_5[] = {1, 4, 6, 4, 1};
+
+static void test_pascal_row(void) {
+    uint32_t *row = NULL;
+    size_t len = 0;
+
+    CHECK(pascal_row(5, &row, &len) == MS_STATUS_OK);
+    CHECK(len == 5);
+    CHECK(memcmp(row, ROW_5, sizeof(ROW_5)) == 0);
+    CHECK(deallocate_vec(row, len) == MS_STATUS_OK);
+
+    CHECK(pascal_row(0, &row, &len) == MS_STATUS_OK);
+    CHECK(len == 0);
+    CHECK(deallocate_vec(row, len) == MS_STATUS_OK);
+
+    CHECK(pascal_row(36, &row, &len) == MS_STATUS_OVERFLOW);
+    CHECK(row == NULL);
+    CHECK(len == 0);
+    msmodule_clear_error();
+
+    CHECK(pascal_row(5, NULL, &len) == MS_STATUS_NULL_POINTER);
+    msmodule_clear_error();
+}
+
+static void test_pascal_row_into(void) {
+    uint32_t row[8] = {0};
+    size_t len = 0;
+
+    CHECK(pascal_row_len(5, &len) == MS_STATUS_OK);
+    CHECK(len == 5);
+    CHECK(pascal_row_into(5, row, 8) == MS_STATUS_OK);
+    CHECK(memcmp(row, ROW_5, sizeof(ROW_5)) == 0);
+
+    CHECK(pascal_row_into(8, row, 4) == MS_STATUS_BUFFER_TOO_SMALL);
+    msmodule_clear_error();
+
+    CHECK(pascal_row_len(36, &len) == MS_STATUS_OVERFLOW);
+    msmodule_clear_error();
+}
+
+static void test_binomial(void) {
+    uint32_t out = 0;
+
+    CHECK(binomial(30, 15, &out) == MS_STATUS_OK);
+    CHECK(out == 155117520);
+    CHECK(binomial(3, 5, &out) == MS_STATUS_OK);
+    CHECK(out == 0);
+
+    CHECK(binomial(100, 50, &out) == MS_STATUS_OVERFLOW);
+    CHECK(out == 0);
+}
+
+static void test_errors(void) {
+    uint32_t out;
+
+    msmodule_clear_error();
+    CHECK(msmodule_last_error_message() == NULL);
+
+    CHECK(binomial(100, 50, &out) == MS_STATUS_OVERFLOW);
+    CHECK(msmodule_last_error_message() != NULL);
+    CHECK(strcmp(msmodule_last_error_message(),
+                 "C(100, 50) does not fit in uint32") == 0);
+
+    msmodule_clear_error();
+    CHECK(msmodule_last_error_message() == NULL);
+}
+
+static void test_generator(void) {
+    PascalGenerator *gen = msmodule_generator_new();
+    uint32_t row[8] = {0};
+    int i;
+
+    CHECK(gen != NULL);
+    for (i = 0; i < 5; i++) {
+        CHECK(msmodule_generator_next(gen, row, 8) == MS_STATUS_OK);
+    }
+    CHECK(memcmp(row, ROW_5, sizeof(ROW_5)) == 0);
+
+    CHECK(msmodule_generator_next(gen, row, 5) == MS_STATUS_BUFFER_TOO_SMALL);
+    msmodule_clear_error();
+
+    CHECK(msmodule_generator_reset(gen) == MS_STATUS_OK);
+    CHECK(msmodule_generator_next(gen, row, 1) == MS_STATUS_OK);
+    CHECK(row[0] == 1);
+
+    msmodule_generator_free(gen);
+    msmodule_generator_free(NULL);
+}
+
+struct foreach_state {
+    size_t rows;
+    size_t stop_after;
+    uint32_t last[8];
+};
+
+static bool count_rows(const uint32_t *row, size_t len, void *user_data) {
+    struct foreach_state *state = user_data;
+
+    state->rows++;
+    if (len <= 8) {
+        memcpy(state->last, row, len * sizeof(*row));
+    }
+    return state->rows < state->stop_after;
+}
+
+static void test_foreach(void) {
+    struct foreach_state state = {0, 100, {0}};
+
+    CHECK(pascal_rows_foreach(5, count_rows, &state) == MS_STATUS_OK);
+    CHECK(state.rows == 5);
+    CHECK(memcmp(state.last, ROW_5, sizeof(ROW_5)) == 0);
+
+    state.rows = 0;
+    state.stop_after = 2;
+    CHECK(pascal_rows_foreach(5, count_rows, &state) == MS_STATUS_OK);
+    CHECK(state.rows == 2);
+
+    state.rows = 0;
+    state.stop_after = 100;
+    CHECK(pascal_rows_foreach(100, count_rows, &state) == MS_STATUS_OVERFLOW);
+    CHECK(state.rows == 35);
+    msmodule_clear_error();
+
+    CHECK(pascal_rows_foreach(5, NULL, &state) == MS_STATUS_NULL_POINTER);
+    msmodule_clear_error();
+}
+
+static void test_date(void) {
+    MsDate date = {2020, 3, 1};
+    MsDate out;
+    int64_t timestamp = 0;
+
+    CHECK(msmodule_date_to_timestamp(date, &timestamp) == MS_STATUS_OK);
+    CHECK(timestamp == 1583020800);
+
+    CHECK(msmodule_date_seconds_before(date, 1, &out) == MS_STATUS_OK);
+    CHECK(out.year == 2020 && out.month == 2 && out.day == 29);
+
+    date.day = 31;
+    date.month = 4;
+    CHECK(msmodule_date_to_timestamp(date, &timestamp) == MS_STATUS_INVALID_ARGUMENT);
+    msmodule_clear_error();
+}
+
+static void test_point(void) {
+    MsPoint point = {3, 4};
+
+    CHECK(msmodule_point_norm(point) == 5.0);
+}
+
+static void test_manifest(void) {
+    const char *manifest = msmodule_manifest();
+
+    CHECK(msmodule_abi_version() == MSMODULE_ABI_VERSION);
+    CHECK(manifest != NULL);
+    CHECK(strstr(manifest, "\"name\": \"pascal_row\"") != NULL);
+}
+
+static void test_allocations(void) {
+    uint32_t *row = NULL, *other = NULL;
+    size_t len = 0, other_len = 0, count = 0;
+    MsAllocation allocations[4];
+
+    /*
+     * Only libraries built with the track-allocations feature keep a registry;
+     * tests/c_abi.rs defines MSMODULE_TRACK_ALLOCATIONS for those
+     */
+#ifndef MSMODULE_TRACK_ALLOCATIONS
+    CHECK(msmodule_outstanding_allocations(NULL, 0, &count) == MS_STATUS_UNSUPPORTED);
+    msmodule_clear_error();
+    return;
+#endif
+
+    CHECK(msmodule_outstanding_allocations(NULL, 0, &count) == MS_STATUS_OK);
+    CHECK(count == 0);
+
+    CHECK(pascal_row(5, &row, &len) == MS_STATUS_OK);
+    CHECK(pascal_row(7, &other, &other_len) == MS_STATUS_OK);
+    CHECK(msmodule_outstanding_allocations(allocations, 4, &count) == MS_STATUS_OK);
+    CHECK(count == 2);
+
+    CHECK(deallocate_vec(row, len + 1) == MS_STATUS_INVALID_ARGUMENT);
+    CHECK(deallocate_vec(row, 0) == MS_STATUS_INVALID_ARGUMENT);
+    CHECK(deallocate_vec(row + 1, len - 1) == MS_STATUS_INVALID_ARGUMENT);
+    CHECK(deallocate_vec((uint32_t *)0x1234, 0) == MS_STATUS_INVALID_ARGUMENT);
+    CHECK(strstr(msmodule_last_error_message(), "not returned by pascal_row") != NULL);
+    CHECK(deallocate_vec(row, len) == MS_STATUS_OK);
+    CHECK(deallocate_vec(row, len) == MS_STATUS_INVALID_ARGUMENT);
+    CHECK(strstr(msmodule_last_error_message(), "double free") != NULL);
+    msmodule_clear_error();
+
+    CHECK(msmodule_outstanding_allocations(allocations, 4, &count) == MS_STATUS_OK);
+    CHECK(count == 1);
+    CHECK(allocations[0].ptr == other && allocations[0].len == other_len);
+
+    CHECK(deallocate_vec(other, other_len) == MS_STATUS_OK);
+}
+
+int main(void) {
+    test_pascal_row();
+    test_pascal_row_into();
+    test_binomial();
+    test_errors();
+    test_generator();
+    test_foreach();
+    test_date();
+    test_point();
+    test_manifest();
+    test_allocations();
+
+    if (failures) {
+        fprintf(stderr, "%d check(s) failed\n", failures);
+        return EXIT_FAILURE;
+    }
+
+    return EXIT_SUCCESS;
+}
@@ -0,0 +1,83 @@
+//! Build the cdylib, then build `tests/c/test_abi.c` against the generated
+//! header and the cdylib and run it
+//!
+//! The C compiler is taken from `$CC`, defaulting to `cc`.
+
+#![cfg(unix)]
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Directory containing the cdylib, i.e. the parent of `deps/` holding this
+/// test executable
+fn library_dir() -> PathBuf {
+    let exe = env::current_exe().unwrap();
+    exe.parent().and_then(Path::parent).unwrap().to_path_buf()
+}
+
+/// Build the cdylib into `lib_dir` with the same profile and features as this
+/// test
+///
+/// `cargo test` only builds the library targets that tests link against, which
+/// a cdylib is not, so without this the C program would use whatever library
+/// an earlier `cargo build` left behind, if any.
+fn build_library(manifest_dir: &Path, lib_dir: &Path) {
+    // lib_dir is <target dir>/<profile directory>
+    let mut cargo = Command::new(env!("CARGO"));
+    cargo
+        .args(["build", "--lib", "--manifest-path"])
+        .arg(manifest_dir.join("Cargo.toml"))
+        .arg("--target-dir")
+        .arg(lib_dir.parent().unwrap());
+    match lib_dir.file_name().and_then(|name| name.to_str()) {
+        Some("debug") => {}
+        Some("release") => {
+            cargo.arg("--release");
+        }
+        Some(profile) => {
+            cargo.args(["--profile", profile]);
+        }
+        None => panic!("unexpected target directory {}", lib_dir.display()),
+    }
+    if cfg!(feature = "track-allocations") {
+        cargo.args(["--features", "track-allocations"]);
+    }
+
+    let status = cargo
+        .status()
+        .unwrap_or_else(|err| panic!("failed to run cargo: {}", err));
+    assert!(status.success(), "failed to build the cdylib");
+}
+
+#[test]
+fn c_program_calls_every_export() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let lib_dir = library_dir();
+    let exe = lib_dir.join("test_abi");
+    let cc = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+
+    build_library(manifest_dir, &lib_dir);
+
+    let status = Command::new(&cc)
+        .args(["-std=c11", "-Wall", "-Wextra", "-Werror", "-o"])
+        .arg(&exe)
+        .arg(manifest_dir.join("tests/c/test_abi.c"))
+        .args(if cfg!(feature = "track-allocations") {
+            &["-DMSMODULE_TRACK_ALLOCATIONS"][..]
+        } else {
+            &[]
+        })
+        .arg("-I")
+        .arg(Path::new(env!("MSMODULE_HEADER")).parent().unwrap())
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg("-lmsmodule")
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .status()
+        .unwrap_or_else(|err| panic!("failed to run {}: {}", cc, err));
+    assert!(status.success(), "failed to compile tests/c/test_abi.c");
+
+    let status = Command::new(&exe).status().unwrap();
+    assert!(status.success(), "tests/c/test_abi.c failed");
+}
@@ -1,6 +1,13 @@
+import os
+
 from setuptools import setup
 
 def build_native(spec):
+    # build.rs writes the headers to OUT_DIR unless told otherwise; put them
+    # where find_header below looks for them. msmodule.cdef.h is the variant of
+    # msmodule.h without preprocessor directives, which cffi cannot parse
+    os.environ.setdefault('MSMODULE_HEADER_DIR', 'target')
+
     # build an example rust library
     build = spec.add_external_build(
         cmd=['cargo', 'build', '--release'],
@@ -10,7 +17,7 @@ def build_native(spec):
     spec.add_cffi_module(
         module_path='msmodule._native',
         dylib=lambda: build.find_dylib('msmodule', in_path='target/release'),
-        header_filename=lambda: build.find_header('msmodule.h', in_path='target'),
+        header_filename=lambda: build.find_header('msmodule.cdef.h', in_path='target'),
         rtld_flags=['NOW', 'NODELETE']
     )
 
@@ -0,0 +1,40 @@
+import datetime
+
+import pytest
+
+import msmodule
+
+EPOCH = datetime.date(1970, 1, 1)
+
+
+@pytest.mark.parametrize('d', [
+    datetime.date.min,
+    datetime.date(1969, 12, 31),
+    EPOCH,
+    datetime.date(2000, 2, 29),
+    datetime.date(2019, 3, 1),
+    datetime.date.max,
+])
+def test_to_timestamp(d):
+    assert msmodule.to_timestamp(d) == (d - EPOCH).days * 86400
+
+
+@pytest.mark.parametrize('d,seconds,expected', [
+    (datetime.date(2019, 3, 1), 1, datetime.date(2019, 2, 28)),
+    (datetime.date(2020, 3, 1), 1, datetime.date(2020, 2, 29)),
+    (datetime.date(2019, 3, 1), 86400, datetime.date(2019, 2, 28)),
+    (datetime.date(2019, 3, 1), 86401, datetime.date(2019, 2, 27)),
+    (datetime.date(2019, 3, 1), -86400, datetime.date(2019, 3, 2)),
+    (EPOCH, 0, EPOCH),
+])
+def test_seconds_before(d, seconds, expected):
+    assert msmodule.seconds_before(d, seconds) == expected
+
+
+def test_seconds_before_overflow():
+    with pytest.raises(OverflowError):
+        msmodule.seconds_before(datetime.date.min, 1)
+
+
+def test_point_norm():
+    assert msmodule.Point(3, 4).norm() == 5.0
@@ -0,0 +1,48 @@
+import pytest
+
+import msmodule
+from msmodule._native import ffi, lib
+
+
+def test_overflow_message():
+    with pytest.raises(OverflowError, match=r"C\(100, 50\)"):
+        msmodule.binomial(100, 50)
+
+    # The wrapper clears the error once it has been raised
+    assert lib.msmodule_last_error_message() == ffi.NULL
+
+
+def test_null_pointer():
+    assert lib.binomial(3, 1, ffi.NULL) == lib.MS_STATUS_NULL_POINTER
+    assert ffi.string(lib.msmodule_last_error_message()) == b"out must not be null"
+
+    lib.msmodule_clear_error()
+    assert lib.msmodule_last_error_message() == ffi.NULL
+
+
+def test_buffer_too_small():
+    arr = ffi.new("uint32_t[]", 3)
+    assert lib.pascal_row_into(5, arr, 3) == lib.MS_STATUS_BUFFER_TOO_SMALL
+    assert list(arr) == [0, 0, 0]
+
+    lib.msmodule_clear_error()
+
+
+def test_outstanding_allocations():
+    try:
+        before = sorted(msmodule.outstanding_allocations())
+    except NotImplementedError:
+        pytest.skip("needs a library built with the track-allocations feature")
+
+    row = ffi.new("uint32_t **")
+    size = ffi.new("size_t *")
+    assert lib.pascal_row(5, row, size) == lib.MS_STATUS_OK
+    address = int(ffi.cast("uintptr_t", row[0]))
+    assert sorted(msmodule.outstanding_allocations()) == sorted(before + [(address, 5)])
+
+    assert lib.deallocate_vec(row[0], size[0]) == lib.MS_STATUS_OK
+    assert sorted(msmodule.outstanding_allocations()) == before
+
+    # The wrapper copies into an array owned by cffi, so it is not tracked
+    assert msmodule.pascal_row(5) == [1, 4, 6, 4, 1]
+    assert sorted(msmodule.outstanding_allocations()) == before
@@ -0,0 +1,91 @@
+import pytest
+
+import msmodule
+from msmodule._native import ffi, lib
+
+
+def test_pascal_row_zero():
+    assert msmodule.pascal_row(0) == []
+
+
+def test_pascal_row_one():
+    assert msmodule.pascal_row(1) == [1]
+
+
+def test_pascal_row_huge():
+    # Stops at the first row that overflows, long before running out of memory
+    with pytest.raises(OverflowError):
+        msmodule.pascal_row(2 ** 62)
+
+
+@pytest.mark.parametrize('n', [0, 1, 2, 10, 35])
+def test_pascal_row_into(n):
+    l = ffi.new("size_t *")
+    assert lib.pascal_row_len(n, l) == lib.MS_STATUS_OK
+    assert l[0] == n
+
+    arr = ffi.new("uint32_t[]", n + 1)
+    assert lib.pascal_row_into(n, arr, n + 1) == lib.MS_STATUS_OK
+    assert list(arr)[:n] == msmodule.pascal_row(n)
+    assert arr[n] == 0
+
+
+def test_pascal_row_len_overflow():
+    l = ffi.new("size_t *")
+    assert lib.pascal_row_len(36, l) == lib.MS_STATUS_OVERFLOW
+
+    lib.msmodule_clear_error()
+
+
+def test_pascal_rows():
+    rows = list(msmodule.pascal_rows())
+
+    assert len(rows) == 35
+    assert rows == [msmodule.pascal_row(n) for n in range(1, 36)]
+
+
+def test_generator_reset():
+    gen = ffi.gc(lib.msmodule_generator_new(), lib.msmodule_generator_free)
+    arr = ffi.new("uint32_t[]", 3)
+
+    for _ in range(3):
+        assert lib.msmodule_generator_next(gen, arr, 3) == lib.MS_STATUS_OK
+    assert list(arr) == [1, 2, 1]
+
+    assert lib.msmodule_generator_reset(gen) == lib.MS_STATUS_OK
+    assert lib.msmodule_generator_next(gen, arr, 3) == lib.MS_STATUS_OK
+    assert arr[0] == 1
+
+
+def _collect_rows(n_max, stop_after=None):
+    rows = []
+
+    @ffi.callback("bool(const uint32_t *, size_t, void *)")
+    def callback(row, length, user_data):
+        rows.append(list(ffi.unpack(row, length)))
+        return stop_after is None or len(rows) < stop_after
+
+    return lib.pascal_rows_foreach(n_max, callback, ffi.NULL), rows
+
+
+def test_pascal_rows_foreach():
+    status, rows = _collect_rows(10)
+
+    assert status == lib.MS_STATUS_OK
+    assert rows == [msmodule.pascal_row(n) for n in range(1, 11)]
+
+
+def test_pascal_rows_foreach_stop():
+    status, rows = _collect_rows(10, stop_after=3)
+
+    assert status == lib.MS_STATUS_OK
+    assert len(rows) == 3
+
+
+def test_pascal_rows_foreach_overflow():
+    status, rows = _collect_rows(100)
+
+    assert status == lib.MS_STATUS_OVERFLOW
+    assert len(rows) == 35
+
+    lib.msmodule_clear_error()
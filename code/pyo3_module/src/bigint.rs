@@ -0,0 +1,119 @@
+use std::os::raw::c_uchar;
+
+use pyo3::ffi;
+use pyo3::prelude::*;
+
+/// Unsigned arbitrary-precision integer stored as little-endian `u32` limbs
+///
+/// Only the handful of operations needed to build exact Pascal rows are
+/// implemented; the value is converted to a Python `int` at the boundary.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BigUint {
+    limbs: Vec<u32>,
+}
+
+impl BigUint {
+    pub fn zero() -> BigUint {
+        BigUint { limbs: Vec::new() }
+    }
+
+    pub fn one() -> BigUint {
+        BigUint { limbs: vec![1] }
+    }
+
+    /// Number of 32-bit limbs, i.e. the cost of one arithmetic operation
+    pub fn limb_count(&self) -> usize {
+        self.limbs.len()
+    }
+
+    /// Add `other` to `self` in place
+    pub fn add_assign(&mut self, other: &BigUint) {
+        if self.limbs.len() < other.limbs.len() {
+            self.limbs.resize(other.limbs.len(), 0);
+        }
+
+        let mut carry = 0u64;
+        for (i, limb) in self.limbs.iter_mut().enumerate() {
+            let rhs = other.limbs.get(i).cloned().unwrap_or(0);
+            let sum = u64::from(*limb) + u64::from(rhs) + carry;
+            *limb = sum as u32;
+            carry = sum >> 32;
+        }
+
+        if carry != 0 {
+            self.limbs.push(carry as u32);
+        }
+    }
+
+    /// Multiply `self` by a machine word in place
+    pub fn mul_small(&mut self, m: u64) {
+        if m == 0 {
+            self.limbs.clear();
+            return;
+        }
+
+        let mut carry = 0u128;
+        for limb in self.limbs.iter_mut() {
+            let prod = u128::from(*limb) * u128::from(m) + carry;
+            *limb = prod as u32;
+            carry = prod >> 32;
+        }
+
+        while carry != 0 {
+            self.limbs.push(carry as u32);
+            carry >>= 32;
+        }
+    }
+
+    /// Divide `self` by a machine word in place, returning the remainder
+    pub fn div_small(&mut self, d: u64) -> u64 {
+        assert!(d != 0, "division by zero");
+
+        let mut rem = 0u128;
+        for limb in self.limbs.iter_mut().rev() {
+            let cur = (rem << 32) | u128::from(*limb);
+            *limb = (cur / u128::from(d)) as u32;
+            rem = cur % u128::from(d);
+        }
+
+        self.normalize();
+        rem as u64
+    }
+
+    /// Little-endian byte representation without trailing zero bytes
+    pub fn to_bytes_le(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.limbs.len() * 4);
+        for limb in &self.limbs {
+            for shift in 0..4 {
+                bytes.push((limb >> (8 * shift)) as u8);
+            }
+        }
+
+        while bytes.last() == Some(&0) {
+            bytes.pop();
+        }
+
+        bytes
+    }
+
+    fn normalize(&mut self) {
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+    }
+}
+
+impl ToPyObject for BigUint {
+    fn to_object(&self, py: Python) -> PyObject {
+        let bytes = self.to_bytes_le();
+        unsafe {
+            let obj = ffi::_PyLong_FromByteArray(
+                bytes.as_ptr() as *const c_uchar,
+                bytes.len(),
+                1, // little endian
+                0, // unsigned
+            );
+            PyObject::from_owned_ptr_or_panic(py, obj)
+        }
+    }
+}
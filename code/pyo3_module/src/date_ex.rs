@@ -1,44 +1,511 @@
+use std::io;
+use std::os::raw::c_int;
+use std::path::Path;
+
+use pyo3::exceptions::{KeyError, OverflowError, TypeError, ValueError};
+use pyo3::ffi;
 use pyo3::prelude::*;
-use pyo3::types::{PyDate, PyDateAccess};
+use pyo3::types::{
+    PyDate, PyDateAccess, PyDateTime, PyDelta, PyDeltaAccess, PyLong, PyObjectRef, PyTimeAccess,
+    PyTzInfo,
+};
 use pyo3::wrap_pyfunction;
+use pyo3::{PyTypeInfo, ToPyPointer};
+
+use tzif::{LocalTimeType, TimeZone, TzifError, DEFAULT_ZONEINFO_DIR};
+
+/// Smallest and largest years of a `datetime.date`
+pub(crate) const MIN_YEAR: i32 = 1;
+pub(crate) const MAX_YEAR: i32 = 9999;
+
+/// Microseconds, wide enough that no offset in seconds or as a timedelta can
+/// overflow when added to a date
+type Micros = i128;
+
+const MICROS_PER_SECOND: Micros = 1_000_000;
+const MICROS_PER_DAY: Micros = 86400 * MICROS_PER_SECOND;
+
+/// Bound on the microseconds from the Unix epoch to any datetime, which keeps
+/// timestamps well inside the range of `Micros`
+const MAX_TIMESTAMP_MICROS: f64 = 1e18;
+
+/// Days from 0000-03-01 to 1970-01-01
+const UNIX_EPOCH_OFFSET: i64 = 719_468;
 
-static SECONDS_BEFORE_1970 : i64 = 62135596800;
-static DAYS_BEFORE_MONTH : [i32; 12] = [0, 31, 59, 90, 120, 151,
-                                        181, 212, 243, 273, 304, 334];
+/// Days from 1970-01-01 to the proleptic Gregorian date `year`-`month`-`day`
+///
+/// This is Howard Hinnant's `days_from_civil`. Years are counted from March so
+/// that the leap day is the last day of the year, and in 400-year eras, which
+/// all have the same number of days. Only years 1 to 9999 are supported, which
+/// keeps every intermediate value non-negative.
+pub(crate) fn days_from_civil(year: i32, month: u8, day: u8) -> i64 {
+    let year = i64::from(year) - if month <= 2 { 1 } else { 0 };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let month_from_march = (i64::from(month) + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
 
-fn is_leap(year: i32) -> bool {
-    (year % 4 == 0) && (year % 100 != 0 || year % 400 == 0)
+    era * 146_097 + day_of_era - UNIX_EPOCH_OFFSET
 }
 
-/// Calculate epoch time from a PyDate object
-fn to_timestamp(date: &PyDate) -> i64 {
-    let year = date.get_year() - 1;
-    let month = date.get_month() - 1;
-    let day = date.get_day();
+/// Inverse of `days_from_civil`, or `None` if the date is not in years 1 to
+/// 9999
+pub(crate) fn civil_from_days(days: i64) -> Option<(i32, u8, u8)> {
+    if days < days_from_civil(MIN_YEAR, 1, 1) || days > days_from_civil(MAX_YEAR, 12, 31) {
+        return None;
+    }
+
+    let days = days + UNIX_EPOCH_OFFSET;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = era * 400 + year_of_era + if month <= 2 { 1 } else { 0 };
 
-    let mut days = 365 * year + (year / 4) - (year / 100) + (year / 400);
+    Some((year as i32, month as u8, day as u8))
+}
 
-    days += DAYS_BEFORE_MONTH[month as usize];
-    if is_leap(year) && month > 2 || (month == 2 && day == 29) {
-        days += 1;
+/// Largest integer not greater than `a / b`, for positive `b`
+fn floor_div(a: Micros, b: Micros) -> Micros {
+    let quotient = a / b;
+    if a % b < 0 {
+        quotient - 1
+    } else {
+        quotient
     }
+}
+
+/// Error for a result `days` after 1970-01-01 that is not in years 1 to 9999
+fn out_of_range(days: i64) -> PyErr {
+    let message = if days < 0 {
+        "result is before 0001-01-01, the earliest supported date"
+    } else {
+        "result is after 9999-12-31, the latest supported date"
+    };
+
+    OverflowError::py_err(message)
+}
 
-    days += day as i32;
-    let seconds : i64 = (days as i64) * 86400;
-    seconds - SECONDS_BEFORE_1970
+/// Microseconds from 1970-01-01 00:00 to midnight at the start of a date
+fn date_to_micros<D: PyDateAccess>(d: &D) -> Micros {
+    Micros::from(days_from_civil(d.get_year(), d.get_month(), d.get_day())) * MICROS_PER_DAY
+}
+
+/// Microseconds from 1970-01-01 00:00 to the wall-clock time of a PyDateTime
+/// object, ignoring its tzinfo
+fn datetime_to_micros(dt: &PyDateTime) -> Micros {
+    let seconds = (i64::from(dt.get_hour()) * 60 + i64::from(dt.get_minute())) * 60
+        + i64::from(dt.get_second());
+
+    date_to_micros(dt)
+        + Micros::from(seconds) * MICROS_PER_SECOND
+        + Micros::from(dt.get_microsecond())
+}
+
+/// `(year, month, day)` and the microseconds since midnight of the time
+/// `micros` after 1970-01-01 00:00
+fn civil_from_micros(micros: Micros) -> PyResult<((i32, u8, u8), Micros)> {
+    // Any offset fits in an i64 once divided into days
+    let days = floor_div(micros, MICROS_PER_DAY) as i64;
+    let date = civil_from_days(days).ok_or_else(|| out_of_range(days))?;
+
+    Ok((date, micros - Micros::from(days) * MICROS_PER_DAY))
+}
+
+/// The date containing the time `micros` after 1970-01-01 00:00
+fn date_from_micros(py: Python, micros: Micros) -> PyResult<Py<PyDate>> {
+    let ((year, month, day), _) = civil_from_micros(micros)?;
+
+    PyDate::new(py, year, month, day)
+}
+
+/// The datetime whose wall-clock time is `micros` after 1970-01-01 00:00, with
+/// the given tzinfo and fold
+fn datetime_from_micros<T: ToPyPointer>(
+    py: Python,
+    micros: Micros,
+    tzinfo: &T,
+    fold: u8,
+) -> PyResult<Py<PyDateTime>> {
+    let ((year, month, day), micros) = civil_from_micros(micros)?;
+    let seconds = micros / MICROS_PER_SECOND;
+
+    // PyDateTime::new cannot set fold
+    unsafe {
+        let ptr = (ffi::PyDateTimeAPI.DateTime_FromDateAndTimeAndFold)(
+            year,
+            c_int::from(month),
+            c_int::from(day),
+            (seconds / 3600) as c_int,
+            (seconds / 60 % 60) as c_int,
+            (seconds % 60) as c_int,
+            (micros % MICROS_PER_SECOND) as c_int,
+            tzinfo.as_ptr(),
+            c_int::from(fold),
+            ffi::PyDateTimeAPI.DateTimeType,
+        );
+        Py::from_owned_ptr_or_err(py, ptr)
+    }
+}
+
+fn micros_to_delta(py: Python, micros: Micros) -> PyResult<Py<PyDelta>> {
+    let days = floor_div(micros, MICROS_PER_DAY);
+    let micros = micros - days * MICROS_PER_DAY;
+
+    PyDelta::new(
+        py,
+        days as i32,
+        (micros / MICROS_PER_SECOND) as i32,
+        (micros % MICROS_PER_SECOND) as i32,
+        false,
+    )
+}
+
+fn seconds_to_delta(py: Python, seconds: i64) -> PyResult<Py<PyDelta>> {
+    micros_to_delta(py, Micros::from(seconds) * MICROS_PER_SECOND)
+}
+
+fn delta_to_micros(delta: &PyDelta) -> Micros {
+    Micros::from(delta.get_days()) * MICROS_PER_DAY
+        + Micros::from(delta.get_seconds()) * MICROS_PER_SECOND
+        + Micros::from(delta.get_microseconds())
+}
+
+/// An offset given as a timedelta or a whole number of seconds, in
+/// microseconds
+fn offset_to_micros(offset: &PyObjectRef) -> PyResult<Micros> {
+    if let Ok(delta) = offset.cast_as::<PyDelta>() {
+        Ok(delta_to_micros(delta))
+    } else if offset.cast_as::<PyLong>().is_ok() {
+        Ok(Micros::from(offset.extract::<i64>()?) * MICROS_PER_SECOND)
+    } else {
+        Err(TypeError::py_err(format!(
+            "offset must be an int or timedelta, not {}",
+            offset.get_type().name()
+        )))
+    }
+}
+
+fn not_a_date(d: &PyObjectRef) -> PyErr {
+    TypeError::py_err(format!(
+        "expected a date or datetime, not {}",
+        d.get_type().name()
+    ))
+}
+
+/// Raise ValueError unless `dt.tzinfo` is the Python object of `tz`, as
+/// `tzinfo.fromutc` does
+fn check_fromutc<T: PyTypeInfo>(tz: &T, dt: &PyDateTime) -> PyResult<()> {
+    let slf = unsafe { (tz as *const T as *mut u8).offset(-T::OFFSET) } as *mut ffi::PyObject;
+    if dt.getattr("tzinfo")?.as_ptr() != slf {
+        return Err(ValueError::py_err("fromutc: dt.tzinfo is not self"));
+    }
+
+    Ok(())
+}
+
+/// A date or datetime moved `micros` microseconds later
+fn shift_by(py: Python, d: &PyObjectRef, micros: Micros) -> PyResult<PyObject> {
+    // datetime is a subclass of date, so must be checked for first
+    if let Ok(dt) = d.cast_as::<PyDateTime>() {
+        let result = datetime_from_micros(
+            py,
+            datetime_to_micros(dt) + micros,
+            dt.getattr("tzinfo")?,
+            dt.get_fold(),
+        )?;
+        Ok(result.into_object(py))
+    } else if let Ok(d) = d.cast_as::<PyDate>() {
+        Ok(date_from_micros(py, date_to_micros(d) + micros)?.into_object(py))
+    } else {
+        Err(not_a_date(d))
+    }
 }
 
 #[pyfunction]
-fn seconds_before(py: Python, d: &PyDate, seconds: i64) -> PyResult<Py<PyDate>> {
-    let timestamp = to_timestamp(&d);
+/// `seconds` before a date or datetime
+///
+/// `seconds` is an int or a timedelta. A date is treated as midnight UTC at its
+/// start, and the result is the date containing the earlier instant. All the
+/// arithmetic is in UTC, so the result does not depend on the local time zone.
+///
+/// A datetime keeps its microseconds, tzinfo and fold. As with subtracting a
+/// `timedelta`, the arithmetic is on the wall-clock time, so an aware result
+/// is not adjusted for changes in its UTC offset.
+///
+/// Raises OverflowError if the result is not in years 1 to 9999.
+fn seconds_before(py: Python, d: &PyObjectRef, seconds: &PyObjectRef) -> PyResult<PyObject> {
+    shift_by(py, d, -offset_to_micros(seconds)?)
+}
 
-    PyDate::from_timestamp(py, timestamp - seconds)
+#[pyfunction]
+/// `seconds` after a date or datetime, the reverse of `seconds_before`
+fn seconds_after(py: Python, d: &PyObjectRef, seconds: &PyObjectRef) -> PyResult<PyObject> {
+    shift_by(py, d, offset_to_micros(seconds)?)
+}
+
+#[pyfunction]
+/// A date or datetime moved by the timedelta `delta`, like `seconds_after`
+fn shift(py: Python, d: &PyObjectRef, delta: &PyDelta) -> PyResult<PyObject> {
+    shift_by(py, d, delta_to_micros(delta))
+}
+
+/// Microseconds from the Unix epoch to a date or datetime
+///
+/// Dates and naive datetimes are taken to be in UTC. Aware datetimes are
+/// converted with their `utcoffset()`.
+fn utc_micros(d: &PyObjectRef) -> PyResult<Micros> {
+    if let Ok(dt) = d.cast_as::<PyDateTime>() {
+        let offset = dt.call_method0("utcoffset")?;
+        if offset.is_none() {
+            Ok(datetime_to_micros(dt))
+        } else {
+            Ok(datetime_to_micros(dt) - delta_to_micros(offset.cast_as::<PyDelta>()?))
+        }
+    } else if let Ok(d) = d.cast_as::<PyDate>() {
+        Ok(date_to_micros(d))
+    } else {
+        Err(not_a_date(d))
+    }
+}
+
+#[pyfunction]
+/// The POSIX timestamp of a date or datetime
+///
+/// Unlike `datetime.timestamp`, dates and naive datetimes are taken to be in
+/// UTC rather than local time, so the result does not depend on the local time
+/// zone. Aware datetimes, including those using `FixedOffset`, are converted
+/// with their `utcoffset()`.
+fn to_utc(d: &PyObjectRef) -> PyResult<f64> {
+    Ok(utc_micros(d)? as f64 / MICROS_PER_SECOND as f64)
+}
+
+#[pyfunction]
+/// The datetime at the POSIX timestamp `ts`
+///
+/// The result is in the time zone `tz` if given, using its `fromutc()`, and is
+/// otherwise a naive datetime in UTC. `ts` is rounded to the nearest
+/// microsecond.
+fn from_utc(py: Python, ts: f64, tz: Option<&PyTzInfo>) -> PyResult<PyObject> {
+    let micros = (ts * MICROS_PER_SECOND as f64).round();
+    // Checked first, as the conversion to an integer is only meaningful for
+    // finite values in range
+    if micros.is_nan() {
+        return Err(ValueError::py_err("timestamp must not be NaN"));
+    } else if micros.abs() > MAX_TIMESTAMP_MICROS {
+        return Err(out_of_range(if micros < 0.0 { -1 } else { 1 }));
+    }
+
+    let micros = micros as Micros;
+    match tz {
+        Some(tz) => {
+            let utc = datetime_from_micros(py, micros, tz, 0)?;
+            Ok(tz.call_method1("fromutc", (utc,))?.to_object(py))
+        }
+        None => Ok(datetime_from_micros(py, micros, &py.None(), 0)?.into_object(py)),
+    }
+}
+
+#[pyclass(extends=PyTzInfo)]
+/// A `datetime.tzinfo` with a constant offset from UTC, like
+/// `datetime.timezone`
+///
+/// `FixedOffset(offset, name=None)` takes the offset east of UTC as a
+/// timedelta or a number of seconds, strictly between -24 and 24 hours. The
+/// name defaults to the form used by `datetime.timezone`, such as
+/// `"UTC+05:30"`.
+struct FixedOffset {
+    offset: Micros,
+    name: String,
+}
+
+impl FixedOffset {
+    fn default_name(offset: Micros) -> String {
+        if offset == 0 {
+            return "UTC".to_string();
+        }
+
+        let sign = if offset < 0 { '-' } else { '+' };
+        let micros = offset.abs() % MICROS_PER_SECOND;
+        let seconds = offset.abs() / MICROS_PER_SECOND;
+        let mut name = format!("UTC{}{:02}:{:02}", sign, seconds / 3600, seconds / 60 % 60);
+        if seconds % 60 != 0 || micros != 0 {
+            name.push_str(&format!(":{:02}", seconds % 60));
+        }
+        if micros != 0 {
+            name.push_str(&format!(".{:06}", micros));
+        }
+
+        name
+    }
+}
+
+#[pymethods]
+impl FixedOffset {
+    #[new]
+    fn __new__(obj: &PyRawObject, offset: &PyObjectRef, name: Option<String>) -> PyResult<()> {
+        let offset = offset_to_micros(offset)?;
+        if offset.abs() >= MICROS_PER_DAY {
+            return Err(ValueError::py_err(
+                "offset must be strictly between -24 and 24 hours",
+            ));
+        }
+
+        let name = name.unwrap_or_else(|| FixedOffset::default_name(offset));
+        obj.init(FixedOffset { offset, name });
+
+        Ok(())
+    }
+
+    fn utcoffset(&self, py: Python, _dt: &PyObjectRef) -> PyResult<Py<PyDelta>> {
+        micros_to_delta(py, self.offset)
+    }
+
+    fn dst(&self, py: Python, _dt: &PyObjectRef) -> PyObject {
+        py.None()
+    }
+
+    fn tzname(&self, _dt: &PyObjectRef) -> String {
+        self.name.clone()
+    }
+
+    /// The local time of the UTC time `dt`, whose tzinfo is this object
+    ///
+    /// `tzinfo.fromutc` needs `dst()` to return a timedelta, so is overridden.
+    /// Raises ValueError if `dt.tzinfo` is not this object.
+    fn fromutc(&self, py: Python, dt: &PyDateTime) -> PyResult<Py<PyDateTime>> {
+        check_fromutc(self, dt)?;
+        datetime_from_micros(
+            py,
+            datetime_to_micros(dt) + self.offset,
+            dt.getattr("tzinfo")?,
+            0,
+        )
+    }
+}
+
+#[pyclass(extends=PyTzInfo)]
+/// A `datetime.tzinfo` for a zone of the IANA time zone database, like
+/// `zoneinfo.ZoneInfo`
+///
+/// `ZoneInfo(key, zoneinfo_dir=None)` reads the TZif file for `key`, such as
+/// `"Europe/London"`, from `zoneinfo_dir`, by default `/usr/share/zoneinfo`.
+/// Raises KeyError if there is no such file.
+///
+/// Wall-clock times that are skipped or repeated when the offset changes are
+/// resolved with `fold` as in PEP 495: 0 gives the offset before the change
+/// and 1 the offset after it.
+struct ZoneInfo {
+    key: String,
+    zone: TimeZone,
+}
+
+impl ZoneInfo {
+    /// Local time type at the wall-clock time of a datetime
+    ///
+    /// For `None`, as passed by `datetime.time`, this is the zone's only local
+    /// time type, if it has just one.
+    fn find(&self, dt: &PyObjectRef) -> PyResult<Option<&LocalTimeType>> {
+        if dt.is_none() {
+            let fixed = if self.zone.is_fixed() {
+                Some(self.zone.find_utc(0).0)
+            } else {
+                None
+            };
+            return Ok(fixed);
+        }
+
+        let dt = dt.cast_as::<PyDateTime>()?;
+        let wall = floor_div(datetime_to_micros(dt), MICROS_PER_SECOND) as i64;
+        Ok(Some(self.zone.find_local(wall, dt.get_fold() != 0)))
+    }
+}
+
+#[pymethods]
+impl ZoneInfo {
+    #[new]
+    fn __new__(obj: &PyRawObject, key: String, zoneinfo_dir: Option<String>) -> PyResult<()> {
+        let dir = zoneinfo_dir.unwrap_or_else(|| DEFAULT_ZONEINFO_DIR.to_string());
+        let zone = TimeZone::load(Path::new(&dir), &key).map_err(|err| zone_error(&key, err))?;
+        obj.init(ZoneInfo { key, zone });
+
+        Ok(())
+    }
+
+    #[getter]
+    fn key(&self) -> PyResult<String> {
+        Ok(self.key.clone())
+    }
+
+    fn utcoffset(&self, py: Python, dt: &PyObjectRef) -> PyResult<PyObject> {
+        match self.find(dt)? {
+            Some(ty) => Ok(seconds_to_delta(py, ty.utc_offset)?.into_object(py)),
+            None => Ok(py.None()),
+        }
+    }
+
+    fn dst(&self, py: Python, dt: &PyObjectRef) -> PyResult<PyObject> {
+        match self.find(dt)? {
+            Some(ty) => Ok(seconds_to_delta(py, ty.dst)?.into_object(py)),
+            None => Ok(py.None()),
+        }
+    }
+
+    fn tzname(&self, py: Python, dt: &PyObjectRef) -> PyResult<PyObject> {
+        match self.find(dt)? {
+            Some(ty) => Ok(ty.abbreviation.to_object(py)),
+            None => Ok(py.None()),
+        }
+    }
+
+    /// The local time of the UTC time `dt`, whose tzinfo is this object,
+    /// with `fold` set if the wall-clock time is the second of two
+    ///
+    /// Raises ValueError if `dt.tzinfo` is not this object.
+    fn fromutc(&self, py: Python, dt: &PyDateTime) -> PyResult<Py<PyDateTime>> {
+        check_fromutc(self, dt)?;
+        let micros = datetime_to_micros(dt);
+        let (ty, fold) = self
+            .zone
+            .find_utc(floor_div(micros, MICROS_PER_SECOND) as i64);
+        let local = micros + Micros::from(ty.utc_offset) * MICROS_PER_SECOND;
+
+        datetime_from_micros(py, local, dt.getattr("tzinfo")?, fold as u8)
+    }
+}
+
+/// Error for the time zone `key` that could not be loaded
+fn zone_error(key: &str, err: TzifError) -> PyErr {
+    match err {
+        TzifError::InvalidKey(_) => ValueError::py_err(format!("invalid time zone key {:?}", key)),
+        TzifError::Io(ref err) if err.kind() == io::ErrorKind::NotFound => {
+            KeyError::py_err(format!("no time zone found with key {:?}", key))
+        }
+        TzifError::Io(err) => err.into(),
+        TzifError::Malformed(message) => {
+            ValueError::py_err(format!("invalid TZif file for {:?}: {}", key, message))
+        }
+    }
 }
 
 #[pymodule]
 fn date_ex(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_wrapped(wrap_pyfunction!(seconds_before))?;
+    m.add_wrapped(wrap_pyfunction!(seconds_after))?;
+    m.add_wrapped(wrap_pyfunction!(shift))?;
+    m.add_wrapped(wrap_pyfunction!(to_utc))?;
+    m.add_wrapped(wrap_pyfunction!(from_utc))?;
+    m.add_class::<FixedOffset>()?;
+    m.add_class::<ZoneInfo>()?;
 
     Ok(())
 }
-
@@ -0,0 +1,250 @@
+//! Factorials modulo a prime too large to tabulate them all
+//!
+//! Only every vth factorial is tabulated, for v around sqrt(p). With
+//! g_d(x) = (vx + 1)(vx + 2)...(vx + d), these are products of the values
+//! g_v(0), g_v(1), ..., which are computed with the sample-point shifting
+//! algorithm: g_d(0..=d) determine g_d as a polynomial, so the samples of g_2d
+//! can be obtained from those of g_d by Lagrange interpolation, which is a
+//! convolution. Building g_v up by doubling d costs O(sqrt(p) log p) instead
+//! of the O(p) needed to multiply everything out.
+
+use modular::{inv_mod, inv_mod_all, mul_mod, pow_mod};
+
+/// A prime of the form c * 2^k + 1 with 3 as a primitive root, for the
+/// number-theoretic transform
+///
+/// Making the prime a constant lets the compiler replace each `% Q` with
+/// multiplications, which is most of the cost of a transform.
+trait NttPrime {
+    const Q: u64;
+}
+
+struct Ntt1;
+struct Ntt2;
+struct Ntt3;
+
+impl NttPrime for Ntt1 {
+    const Q: u64 = 998_244_353;
+}
+
+impl NttPrime for Ntt2 {
+    const Q: u64 = 167_772_161;
+}
+
+impl NttPrime for Ntt3 {
+    const Q: u64 = 469_762_049;
+}
+
+/// In-place number-theoretic transform modulo `P::Q`, inverted if `invert`
+fn ntt<P: NttPrime>(a: &mut [u64], invert: bool) {
+    let q = P::Q;
+    let n = a.len();
+
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            a.swap(i, j);
+        }
+    }
+
+    let mut roots = Vec::with_capacity(n / 2);
+    let mut len = 2;
+    while len <= n {
+        let mut w_len = pow_mod(3, (q - 1) / len as u64, q);
+        if invert {
+            w_len = inv_mod(w_len, q);
+        }
+        roots.clear();
+        roots.push(1);
+        for i in 1..len / 2 {
+            let prev = roots[i - 1];
+            roots.push(prev * w_len % q);
+        }
+
+        for chunk in a.chunks_mut(len) {
+            let (lo, hi) = chunk.split_at_mut(len / 2);
+            for ((x, y), &w) in lo.iter_mut().zip(hi.iter_mut()).zip(&roots) {
+                let (u, v) = (*x, *y * w % q);
+                *x = if u + v >= q { u + v - q } else { u + v };
+                *y = if u >= v { u - v } else { u + q - v };
+            }
+        }
+        len <<= 1;
+    }
+
+    if invert {
+        let n_inv = inv_mod(n as u64, q);
+        for x in a.iter_mut() {
+            *x = *x * n_inv % q;
+        }
+    }
+}
+
+/// The cyclic convolution of `a` and `b`, of length `size`, modulo `P::Q`
+fn ntt_convolve<P: NttPrime>(a: &[u64], b: &[u64], size: usize) -> Vec<u64> {
+    let q = P::Q;
+    let mut fa = a.iter().map(|&x| x % q).collect::<Vec<_>>();
+    let mut fb = b.iter().map(|&x| x % q).collect::<Vec<_>>();
+    fa.resize(size, 0);
+    fb.resize(size, 0);
+    ntt::<P>(&mut fa, false);
+    ntt::<P>(&mut fb, false);
+    for (x, y) in fa.iter_mut().zip(&fb) {
+        *x = *x * y % q;
+    }
+    ntt::<P>(&mut fa, true);
+
+    fa
+}
+
+/// Coefficients `lo..hi` of the product of `a` and `b`, reduced modulo `p`
+///
+/// The product is computed modulo three NTT primes, whose product exceeds any
+/// coefficient of a product of residues below 2^32 with fewer than 2^22 terms.
+fn convolve(a: &[u64], b: &[u64], lo: usize, hi: usize, p: u64) -> Vec<u64> {
+    let size = (a.len() + b.len() - 1).next_power_of_two();
+    let r1 = ntt_convolve::<Ntt1>(a, b, size);
+    let r2 = ntt_convolve::<Ntt2>(a, b, size);
+    let r3 = ntt_convolve::<Ntt3>(a, b, size);
+
+    // Garner's algorithm, with the last step done modulo p
+    let (q1, q2, q3) = (Ntt1::Q, Ntt2::Q, Ntt3::Q);
+    let q1_inv = inv_mod(q1, q2);
+    let q12_inv = inv_mod(q1 * q2 % q3, q3);
+    let q12 = mul_mod(q1 % p, q2 % p, p);
+
+    (lo..hi)
+        .map(|i| {
+            let t2 = (r2[i] + q2 - r1[i] % q2) % q2 * q1_inv % q2;
+            let x12 = (r1[i] + q1 * t2) % q3;
+            let t3 = (r3[i] + q3 - x12) % q3 * q12_inv % q3;
+            ((r1[i] % p + mul_mod(q1 % p, t2 % p, p)) % p + mul_mod(q12, t3 % p, p)) % p
+        })
+        .collect()
+}
+
+/// Given h(0), ..., h(d) for a polynomial h of degree d, compute h(m), ...,
+/// h(m + count - 1) modulo `p`
+///
+/// None of m - d, ..., m + count - 1 may be 0 mod p.
+fn shift(h: &[u64], m: u64, count: usize, p: u64) -> Vec<u64> {
+    let d = h.len() - 1;
+
+    // h(m + k) = prod_j (m + k - j) * sum_i a_i / (m + k - i), with a_i the
+    // sample h(i) divided by prod_{j != i} (i - j) = (-1)^(d - i) i! (d - i)!
+    let mut fact = vec![1; d + 1];
+    for i in 1..=d {
+        fact[i] = mul_mod(fact[i - 1], i as u64, p);
+    }
+    let mut inv_fact = vec![inv_mod(fact[d], p); d + 1];
+    for i in (1..=d).rev() {
+        inv_fact[i - 1] = mul_mod(inv_fact[i], i as u64, p);
+    }
+    let a = (0..=d)
+        .map(|i| {
+            let a = mul_mod(h[i], mul_mod(inv_fact[i], inv_fact[d - i], p), p);
+            if (d - i) % 2 == 1 && a != 0 { p - a } else { a }
+        })
+        .collect::<Vec<_>>();
+
+    // b_t = 1 / (m - d + t) for t < d + count
+    let base = (m + p - d as u64 % p) % p;
+    let points = (0..(d + count) as u64).map(|t| (base + t) % p).collect::<Vec<_>>();
+    let mut b = points.clone();
+    inv_mod_all(&mut b, p);
+
+    let sums = convolve(&a, &b, d, d + count, p);
+
+    // prod_j (m + k - j) for k = 0, advanced one k at a time
+    let mut numer = points[..=d].iter().fold(1, |acc, &x| mul_mod(acc, x, p));
+    let mut values = Vec::with_capacity(count);
+    for k in 0..count {
+        values.push(mul_mod(numer, sums[k], p));
+        if k + 1 < count {
+            numer = mul_mod(mul_mod(numer, points[k + d + 1], p), b[k], p);
+        }
+    }
+
+    values
+}
+
+/// Factorials modulo a prime p above 2^20
+///
+/// (xv)! is tabulated for x <= v, where v^2 is just above p / 2, after which
+/// any n! up to p / 2 takes fewer than v multiplications, and larger n! are
+/// reduced to those by Wilson's theorem.
+pub struct FactorialTable {
+    p: u64,
+    v: u64,
+    blocks: Vec<u32>,
+}
+
+impl FactorialTable {
+    pub fn new(p: u64) -> FactorialTable {
+        let mut v = ((p / 2) as f64).sqrt() as u64;
+        while v * v <= p / 2 {
+            v += 1;
+        }
+
+        // g holds g_d(0..=d), starting from g_1(x) = vx + 1
+        let mut g = vec![1, v + 1];
+        let v_inv = inv_mod(v, p);
+        for bit in (0..63 - v.leading_zeros()).rev() {
+            let d = (g.len() - 1) as u64;
+            let offset = mul_mod(d, v_inv, p);
+            let mut lower = g.clone();
+            lower.extend(shift(&g, d + 1, d as usize, p));
+            let upper = shift(&g, offset, 2 * d as usize + 1, p);
+            // g_2d(x) = g_d(x) * g_d(x + d / v)
+            g = lower.iter().zip(&upper).map(|(&x, &y)| mul_mod(x, y, p)).collect();
+
+            if (v >> bit) & 1 == 1 {
+                let d = (g.len() - 1) as u64;
+                for (x, value) in g.iter_mut().enumerate() {
+                    *value = mul_mod(*value, (v * x as u64 + d + 1) % p, p);
+                }
+                let last = (1..=d + 1).fold(1, |acc, i| mul_mod(acc, (v * (d + 1) + i) % p, p));
+                g.push(last);
+            }
+        }
+
+        // g_v(x) = ((x + 1) v)! / (xv)!
+        let mut blocks = Vec::with_capacity(v as usize + 1);
+        blocks.push(1);
+        for x in 0..v as usize {
+            let prev = u64::from(blocks[x]);
+            blocks.push(mul_mod(prev, g[x], p) as u32);
+        }
+
+        FactorialTable { p: p, v: v, blocks: blocks }
+    }
+
+    /// n! mod p
+    pub fn factorial(&self, n: u64) -> u64 {
+        let p = self.p;
+        if n >= p {
+            return 0;
+        }
+
+        // By Wilson's theorem, n! (p - 1 - n)! = (-1)^(p - n) mod p
+        if n > (p - 1) / 2 {
+            let r = p - 1 - n;
+            let inv = inv_mod(self.factorial(r), p);
+            return if r % 2 == 0 { (p - inv) % p } else { inv };
+        }
+
+        let x = n / self.v;
+        let mut result = u64::from(self.blocks[x as usize]);
+        for i in x * self.v + 1..=n {
+            result = mul_mod(result, i, p);
+        }
+
+        result
+    }
+}
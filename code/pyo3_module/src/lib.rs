@@ -2,41 +2,735 @@ extern crate pyo3;
 
 pub mod date_ex;
 pub mod classy;
+mod bigint;
+mod factorial;
+mod modular;
+mod signals;
+mod tzif;
 
+use std::cmp;
+use std::mem;
+use std::ops::{Add, Div, Rem};
+use std::os::raw::{c_int, c_void};
+use std::ptr;
+
+use pyo3::class::{PyBufferProtocol, PyIterProtocol};
+use pyo3::exceptions::{BufferError, MemoryError, OverflowError, ValueError};
+use pyo3::ffi;
 use pyo3::prelude::*;
 use pyo3::types::{PyList};
 use pyo3::wrap_pyfunction;
+use pyo3::PyTypeInfo;
+
+use bigint::BigUint;
+use modular::BinomialMod;
+use signals::{Interrupted, SignalChecker};
+
+/// Fixed-width unsigned integers that a Pascal row can be accumulated in
+trait RowInt:
+    Copy + PartialEq + Add<Output = Self> + Div<Output = Self> + Rem<Output = Self> + ToPyObject
+{
+    const NAME: &'static str;
+
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn from_usize(v: usize) -> Option<Self>;
+    fn checked_add(self, other: Self) -> Option<Self>;
+    fn checked_mul(self, other: Self) -> Option<Self>;
+}
+
+macro_rules! impl_row_int {
+    ($t:ident) => {
+        impl RowInt for $t {
+            const NAME: &'static str = stringify!($t);
+
+            fn zero() -> $t { 0 }
+            fn one() -> $t { 1 }
+            fn from_usize(v: usize) -> Option<$t> {
+                if (v as u128) <= ($t::max_value() as u128) {
+                    Some(v as $t)
+                } else {
+                    None
+                }
+            }
+            fn checked_add(self, other: $t) -> Option<$t> {
+                $t::checked_add(self, other)
+            }
+            fn checked_mul(self, other: $t) -> Option<$t> {
+                $t::checked_mul(self, other)
+            }
+        }
+    };
+}
+
+impl_row_int!(u32);
+impl_row_int!(u64);
+impl_row_int!(u128);
+
+/// The first entry of the triangle that does not fit in the requested type
+///
+/// `row` and `column` are zero-based, i.e. the entry is C(row, column), which
+/// is in row `row + 1` as numbered by `pascal_row`.
+#[derive(Clone, Copy, Debug)]
+struct Overflow {
+    row: usize,
+    column: usize,
+    dtype: &'static str,
+}
+
+impl From<Overflow> for PyErr {
+    fn from(err: Overflow) -> PyErr {
+        OverflowError::py_err(format!(
+            "C({}, {}) does not fit in {}",
+            err.row, err.column, err.dtype
+        ))
+    }
+}
+
+/// A row with more entries than could ever be allocated, whatever their values
+#[derive(Clone, Copy, Debug)]
+struct RowTooLong {
+    n: usize,
+}
+
+impl From<RowTooLong> for PyErr {
+    fn from(err: RowTooLong) -> PyErr {
+        MemoryError::py_err(format!("row {} of Pascal's triangle is too long to allocate", err.n))
+    }
+}
+
+/// Reasons a computation can stop without producing a result
+#[derive(Debug)]
+enum ComputeError {
+    Overflow(Overflow),
+    RowTooLong(RowTooLong),
+    Interrupted(Interrupted),
+}
+
+impl From<Overflow> for ComputeError {
+    fn from(err: Overflow) -> ComputeError {
+        ComputeError::Overflow(err)
+    }
+}
 
-fn pascal_row_impl(n: usize) -> Vec<u32> {
-    let mut row : Vec<u32> = Vec::with_capacity(n);
-    row.resize(n, 0);       // Allocate an array of 0s
-    row[0] = 1;
+impl From<RowTooLong> for ComputeError {
+    fn from(err: RowTooLong) -> ComputeError {
+        ComputeError::RowTooLong(err)
+    }
+}
 
-    let mut last : u32;
+impl From<Interrupted> for ComputeError {
+    fn from(err: Interrupted) -> ComputeError {
+        ComputeError::Interrupted(err)
+    }
+}
+
+impl From<ComputeError> for PyErr {
+    fn from(err: ComputeError) -> PyErr {
+        match err {
+            ComputeError::Overflow(err) => err.into(),
+            ComputeError::RowTooLong(err) => err.into(),
+            ComputeError::Interrupted(err) => err.into(),
+        }
+    }
+}
+
+/// Compute the nth row of Pascal's triangle, which is empty for n == 0
+///
+/// The row grows by one entry per iteration rather than being allocated up
+/// front, so that a huge `n` overflows after a few dozen rows instead of
+/// first trying to allocate all `n` entries.
+fn pascal_row_impl<T: RowInt>(n: usize, signals: &mut SignalChecker) -> Result<Vec<T>, ComputeError> {
+    if n == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut row : Vec<T> = vec![T::one()];
+
+    let mut last : T;
     for i in 1..n {
-        let mut curr : u32 = 1;
-        for j in 1..(i + 1) {
+        let mut curr : T = T::one();
+        for j in 1..i {
             last = curr;
             curr = row[j];
-            row[j] = last + curr;
+            row[j] = match last.checked_add(curr) {
+                Some(v) => v,
+                None => return Err(Overflow { row: i, column: j, dtype: T::NAME }.into()),
+            };
         }
+        row.push(T::one());
+        signals.tick(i)?;
     }
 
-    row
+    Ok(row)
 }
 
+/// Check that a row of `n` entries of type `T` is not too long to allocate
+///
+/// `Vec` panics with "capacity overflow" past `isize::MAX` bytes, and a panic
+/// must not unwind into Python.
+fn check_row_len<T>(n: usize) -> Result<(), RowTooLong> {
+    match n.checked_mul(mem::size_of::<T>()) {
+        Some(bytes) if bytes <= isize::max_value() as usize => Ok(()),
+        _ => Err(RowTooLong { n: n }),
+    }
+}
+
+/// Exact version of `pascal_row_impl` for rows whose entries exceed `u32`
+///
+/// Rather than summing the whole triangle with big integers, the row is built
+/// directly from C(m, k + 1) = C(m, k) * (m - k) / (k + 1), where the division
+/// is always exact. Only the first half is computed; the rest is mirrored.
+///
+/// Entries never overflow here, so a huge `n` is rejected up front, and the
+/// row still grows one entry at a time so that it can be interrupted before
+/// running out of memory.
+fn pascal_row_exact_impl(n: usize, signals: &mut SignalChecker) -> Result<Vec<BigUint>, ComputeError> {
+    check_row_len::<BigUint>(n)?;
+
+    let mut row : Vec<BigUint> = Vec::new();
+    let m = n.saturating_sub(1);
+
+    let mut curr = BigUint::one();
+    for k in 0..(n + 1) / 2 {
+        row.push(curr.clone());
+        curr.mul_small((m - k) as u64);
+        curr.div_small((k + 1) as u64);
+        signals.tick(curr.limb_count())?;
+    }
+
+    for k in (n + 1) / 2..n {
+        let mirrored = row[m - k].clone();
+        row.push(mirrored);
+    }
+
+    Ok(row)
+}
+
+/// Replace row `i - 1` of the triangle (of length `i`) with row `i` in place
+///
+/// Every entry is checked before any is written, so `row` is left untouched
+/// if an entry overflows.
+fn next_row<T: RowInt>(row: &mut Vec<T>) -> Result<(), Overflow> {
+    let i = row.len();
+    if let Some(j) = (1..i).find(|&j| row[j - 1].checked_add(row[j]).is_none()) {
+        return Err(Overflow { row: i, column: j, dtype: T::NAME });
+    }
+
+    // Right to left, so that row[j - 1] still holds the old value
+    for j in (1..i).rev() {
+        row[j] = row[j - 1] + row[j];
+    }
+    row.push(T::one());
+
+    Ok(())
+}
+
+/// Exact version of `next_row`, which updates the row in place
+fn next_row_exact(row: &mut Vec<BigUint>) {
+    let i = row.len();
+    row.push(BigUint::one());
+
+    for j in (1..i).rev() {
+        let (left, right) = row.split_at_mut(j);
+        right[0].add_assign(&left[j - 1]);
+    }
+}
+
+fn gcd<T: RowInt>(mut a: T, mut b: T) -> T {
+    while b != T::zero() {
+        let t = a % b;
+        a = b;
+        b = t;
+    }
+
+    a
+}
+
+/// Compute C(n, k) without building the row it is in
+///
+/// This uses C(n, i + 1) = C(n, i) * (n - i) / (i + 1), dividing out the
+/// common factor of C(n, i) and i + 1 first so that no intermediate value is
+/// larger than the result.
+fn binomial_impl<T: RowInt>(n: usize, k: usize) -> Result<T, Overflow> {
+    if k > n {
+        return Ok(T::zero());
+    }
+
+    let overflow = Overflow { row: n, column: k, dtype: T::NAME };
+    let k = cmp::min(k, n - k);
+
+    let mut result = T::one();
+    for i in 0..k {
+        // Every C(n, i) with 0 < i < n is at least n, so n - i must fit
+        let num = T::from_usize(n - i).ok_or(overflow)?;
+        let den = T::from_usize(i + 1).ok_or(overflow)?;
+        let g = gcd(result, den);
+        result = (result / g).checked_mul(num / (den / g)).ok_or(overflow)?;
+    }
+
+    Ok(result)
+}
+
+/// Exact version of `binomial_impl`
+///
+/// Unlike the fixed-width version, which overflows after a few hundred steps
+/// at most, this can run for a long time and so checks for signals.
+fn binomial_exact_impl(n: usize, k: usize, signals: &mut SignalChecker) -> Result<BigUint, Interrupted> {
+    if k > n {
+        return Ok(BigUint::zero());
+    }
+
+    let k = cmp::min(k, n - k);
+
+    let mut result = BigUint::one();
+    for i in 0..k {
+        result.mul_small((n - i) as u64);
+        result.div_small((i + 1) as u64);
+        signals.tick(result.limb_count())?;
+    }
+
+    Ok(result)
+}
+
+fn check_modulus(m: u32) -> PyResult<u64> {
+    if m == 0 {
+        return Err(ValueError::py_err("modulus must be positive"));
+    }
+
+    Ok(u64::from(m))
+}
+
+/// Version of `pascal_row_exact_impl` with every entry reduced modulo `m`
+fn pascal_row_mod_impl(n: usize, table: &BinomialMod, signals: &mut SignalChecker) -> Result<Vec<u64>, ComputeError> {
+    check_row_len::<u64>(n)?;
+
+    let m = n.saturating_sub(1);
+    let mut row = table.row(m as u64, (n + 1) / 2, signals)?;
+
+    for k in (n + 1) / 2..n {
+        let mirrored = row[m - k];
+        row.push(mirrored);
+    }
+
+    Ok(row)
+}
+
+/// Entries converted to Python objects between two releases of the GIL
+const CONVERT_CHUNK: usize = 1 << 10;
+
+/// Convert `items` to a list, letting other threads run every `CONVERT_CHUNK`
+/// items
+///
+/// Unlike computing a row, this needs the GIL, and for big integers it can
+/// take about as long.
+fn to_list<T: ToPyObject>(py: Python, items: &[T]) -> PyObject {
+    let mut objects = Vec::with_capacity(items.len());
+    for chunk in items.chunks(CONVERT_CHUNK) {
+        objects.extend(chunk.iter().map(|item| item.to_object(py)));
+        py.allow_threads(|| ());
+    }
+
+    PyList::new(py, &objects).to_object(py)
+}
+
+/// Integer representation used for the entries of a row
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Dtype {
+    U32,
+    U64,
+    U128,
+    Exact,
+}
+
+impl Dtype {
+    /// Resolve the `exact` and `dtype` arguments shared by the Python API
+    fn from_args(exact: bool, dtype: Option<&str>) -> PyResult<Dtype> {
+        match (exact, dtype) {
+            (true, None) => Ok(Dtype::Exact),
+            (true, Some(_)) => Err(ValueError::py_err("exact and dtype are mutually exclusive")),
+            (false, None) | (false, Some("u32")) => Ok(Dtype::U32),
+            (false, Some("u64")) => Ok(Dtype::U64),
+            (false, Some("u128")) => Ok(Dtype::U128),
+            (false, Some(other)) => {
+                Err(ValueError::py_err(format!("unsupported dtype: {:?}", other)))
+            }
+        }
+    }
+}
+
+/// A row of Pascal's triangle in one of the supported representations
+enum Row {
+    U32(Vec<u32>),
+    U64(Vec<u64>),
+    U128(Vec<u128>),
+    Exact(Vec<BigUint>),
+}
+
+impl Row {
+    /// Compute the nth row in the representation chosen by `dtype`
+    fn compute(n: usize, dtype: Dtype) -> Result<Row, ComputeError> {
+        let signals = &mut SignalChecker::new();
+        let row = match dtype {
+            Dtype::U32 => Row::U32(pascal_row_impl(n, signals)?),
+            Dtype::U64 => Row::U64(pascal_row_impl(n, signals)?),
+            Dtype::U128 => Row::U128(pascal_row_impl(n, signals)?),
+            Dtype::Exact => Row::Exact(pascal_row_exact_impl(n, signals)?),
+        };
+
+        Ok(row)
+    }
+
+    /// The empty row that precedes the first row of the triangle
+    fn empty(dtype: Dtype) -> Row {
+        match dtype {
+            Dtype::U32 => Row::U32(Vec::new()),
+            Dtype::U64 => Row::U64(Vec::new()),
+            Dtype::U128 => Row::U128(Vec::new()),
+            Dtype::Exact => Row::Exact(Vec::new()),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Row::U32(row) => row.len(),
+            Row::U64(row) => row.len(),
+            Row::U128(row) => row.len(),
+            Row::Exact(row) => row.len(),
+        }
+    }
+
+    fn advance(&mut self) -> Result<(), Overflow> {
+        match self {
+            Row::U32(row) => next_row(row),
+            Row::U64(row) => next_row(row),
+            Row::U128(row) => next_row(row),
+            Row::Exact(row) => {
+                next_row_exact(row);
+                Ok(())
+            }
+        }
+    }
+
+    /// Append the entries of `other`, which must have the same dtype
+    fn extend(&mut self, other: &Row) {
+        match (self, other) {
+            (Row::U32(row), Row::U32(other)) => row.extend_from_slice(other),
+            (Row::U64(row), Row::U64(other)) => row.extend_from_slice(other),
+            (Row::U128(row), Row::U128(other)) => row.extend_from_slice(other),
+            (Row::Exact(row), Row::Exact(other)) => row.extend_from_slice(other),
+            _ => panic!("cannot mix rows with different dtypes"),
+        }
+    }
+
+    fn to_object(&self, py: Python) -> PyObject {
+        match self {
+            Row::U32(row) => to_list(py, row),
+            Row::U64(row) => to_list(py, row),
+            Row::U128(row) => to_list(py, row),
+            Row::Exact(row) => to_list(py, row),
+        }
+    }
+}
+
+/// The first `n` rows of Pascal's triangle, stored back to back
+///
+/// Row `i` (of length `i + 1`) starts at offset `i * (i + 1) / 2`.
+struct Triangle {
+    n: usize,
+    entries: Row,
+}
+
+impl Triangle {
+    /// Compute the triangle in a single pass over one working row
+    fn compute(n: usize, dtype: Dtype) -> Result<Triangle, ComputeError> {
+        let mut signals = SignalChecker::new();
+        let mut row = Row::empty(dtype);
+        let mut entries = Row::empty(dtype);
+
+        for _ in 0..n {
+            row.advance()?;
+            entries.extend(&row);
+            signals.tick(row.len())?;
+        }
+
+        Ok(Triangle { n: n, entries: entries })
+    }
+
+    /// Convert to a list containing one list per row
+    fn to_object(&self, py: Python) -> PyObject {
+        fn rows<T: ToPyObject>(py: Python, n: usize, entries: &[T]) -> PyObject {
+            let mut rows = Vec::with_capacity(n);
+            let mut start = 0;
+            for i in 0..n {
+                rows.push(to_list(py, &entries[start..start + i + 1]));
+                start += i + 1;
+            }
+
+            PyList::new(py, &rows).to_object(py)
+        }
+
+        match &self.entries {
+            Row::U32(entries) => rows(py, self.n, entries),
+            Row::U64(entries) => rows(py, self.n, entries),
+            Row::U128(entries) => rows(py, self.n, entries),
+            Row::Exact(entries) => rows(py, self.n, entries),
+        }
+    }
+}
+
+
+#[pyfunction(exact = false)]
+/// Get the nth row of Pascal's triangle
+///
+/// If `exact` is true, the entries are computed with arbitrary precision.
+/// Otherwise they are accumulated in `dtype` (one of `"u32"`, `"u64"` or
+/// `"u128"`, default `"u32"`), raising `OverflowError` if any entry does not
+/// fit.
+///
+/// Row 1 is `[1]` and row 0 is the empty list.
+///
+/// The GIL is released while the row is computed, and the computation can be
+/// interrupted with Ctrl-C.
+fn pascal_row(py: Python, n: usize, exact: bool, dtype: Option<&str>) -> PyResult<PyObject> {
+    let dtype = Dtype::from_args(exact, dtype)?;
+    let row = py.allow_threads(move || Row::compute(n, dtype))?;
+
+    Ok(row.to_object(py))
+}
+
+
+#[pyfunction(exact = false)]
+/// Compute the binomial coefficient C(n, k)
+///
+/// `exact` and `dtype` have the same meaning as for `pascal_row`. C(n, k) is
+/// 0 for k > n.
+fn binomial(py: Python, n: usize, k: usize, exact: bool, dtype: Option<&str>) -> PyResult<PyObject> {
+    let value = match Dtype::from_args(exact, dtype)? {
+        Dtype::U32 => py.allow_threads(move || binomial_impl::<u32>(n, k))?.to_object(py),
+        Dtype::U64 => py.allow_threads(move || binomial_impl::<u64>(n, k))?.to_object(py),
+        Dtype::U128 => py.allow_threads(move || binomial_impl::<u128>(n, k))?.to_object(py),
+        Dtype::Exact => py.allow_threads(move || {
+            binomial_exact_impl(n, k, &mut SignalChecker::new())
+        })?.to_object(py),
+    };
+
+    Ok(value)
+}
+
+
+#[pyfunction(exact = false)]
+/// Get the first `n` rows of Pascal's triangle as a list of lists
+///
+/// `exact` and `dtype` behave as in `pascal_row`. This is much faster than
+/// calling `pascal_row` once per row, since each row is computed from the
+/// previous one.
+fn pascal_triangle(py: Python, n: usize, exact: bool, dtype: Option<&str>) -> PyResult<PyObject> {
+    let dtype = Dtype::from_args(exact, dtype)?;
+    let triangle = py.allow_threads(move || Triangle::compute(n, dtype))?;
+
+    Ok(triangle.to_object(py))
+}
+
+#[pyfunction]
+/// Get the nth row of Pascal's triangle modulo `m`
+///
+/// Each entry is derived from the previous one, so this takes O(n) whatever
+/// the size of `m`.
+fn pascal_row_mod(py: Python, n: usize, m: u32) -> PyResult<PyObject> {
+    let m = check_modulus(m)?;
+    let row = py.allow_threads(move || -> Result<Vec<u64>, ComputeError> {
+        let table = BinomialMod::cached(m);
+        pascal_row_mod_impl(n, &table, &mut SignalChecker::new())
+    })?;
+
+    Ok(PyList::new(py, &row).to_object(py))
+}
 
 #[pyfunction]
-fn pascal_row(py: Python, n: usize) -> PyObject {
-    let list = PyList::new(py, &pascal_row_impl(n));
+/// Compute the binomial coefficient C(n, k) modulo `m`
+///
+/// Prime factors of `m` are handled with Lucas' theorem, so `n` may be as
+/// large as 2**64 - 1. The tables built for `m` are kept for later calls with
+/// the same modulus on the same thread.
+fn binomial_mod(py: Python, n: u64, k: u64, m: u32) -> PyResult<u64> {
+    let m = check_modulus(m)?;
+    let value = py.allow_threads(move || -> Result<u64, ComputeError> {
+        let table = BinomialMod::cached(m);
+        Ok(table.binomial(n, k, &mut SignalChecker::new())?)
+    })?;
+
+    Ok(value)
+}
+
+
+#[pyclass]
+/// Iterator over the rows of Pascal's triangle
+///
+/// Only the current row is kept, and each subsequent row is derived from it
+/// in O(n). `exact` and `dtype` have the same meaning as for `pascal_row`.
+struct PascalTriangle {
+    dtype: Dtype,
+    row: Row,
+}
+
+impl PascalTriangle {
+    // Unlike the free functions, this keeps the GIL: releasing it while `self`
+    // is mutably borrowed would let another thread advance the same iterator.
+    fn advance_by(&mut self, k: usize) -> PyResult<()> {
+        let mut signals = SignalChecker::new();
+        for _ in 0..k {
+            self.row.advance()?;
+            signals.tick(self.row.len())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl PascalTriangle {
+    #[new]
+    #[args(exact = false)]
+    fn __new__(obj: &PyRawObject, exact: bool, dtype: Option<&str>) -> PyResult<()> {
+        let dtype = Dtype::from_args(exact, dtype)?;
+        obj.init(PascalTriangle { dtype: dtype, row: Row::empty(dtype) });
+
+        Ok(())
+    }
+
+    /// The `n` of the current row, as passed to `pascal_row`; 0 before iterating
+    #[getter]
+    fn current_index(&self) -> PyResult<usize> {
+        Ok(self.row.len())
+    }
+
+    /// Advance `k` rows without converting them to Python objects
+    fn skip(&mut self, k: usize) -> PyResult<()> {
+        self.advance_by(k)
+    }
+
+    /// Move to row `n`, so that the next row yielded is row `n + 1`
+    ///
+    /// Seeking backwards restarts from the top of the triangle.
+    fn seek(&mut self, n: usize) -> PyResult<()> {
+        if n < self.row.len() {
+            self.row = Row::empty(self.dtype);
+        }
+
+        let k = n - self.row.len();
+        self.advance_by(k)
+    }
+}
+
+#[pyproto]
+impl<'p> PyIterProtocol for PascalTriangle {
+    fn __iter__(slf: PyRefMut<Self>) -> PyResult<Py<PascalTriangle>> {
+        Ok(slf.into())
+    }
+
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        slf.row.advance()?;
+        Ok(Some(slf.row.to_object(py)))
+    }
+}
+
+
+#[pyclass]
+/// Read-only buffer holding a row of Pascal's triangle
+///
+/// The row stays in the Rust `Vec` it was computed in and is exposed through
+/// the buffer protocol, so `memoryview` and `numpy.frombuffer` can read it
+/// without converting each entry to a Python `int`.
+struct PascalRowBuffer {
+    row: Row,
+    shape: ffi::Py_ssize_t,
+}
+
+#[pyproto]
+impl PyBufferProtocol for PascalRowBuffer {
+    fn bf_getbuffer(&self, view: *mut ffi::Py_buffer, flags: c_int) -> PyResult<()> {
+        if view.is_null() {
+            return Err(BufferError::py_err("View is null"));
+        }
+
+        if (flags & ffi::PyBUF_WRITABLE) == ffi::PyBUF_WRITABLE {
+            return Err(BufferError::py_err("Object is not writable"));
+        }
+
+        let (buf, itemsize, format) = match self.row {
+            Row::U32(ref row) => (row.as_ptr() as *mut c_void, mem::size_of::<u32>(), b"I\0"),
+            Row::U64(ref row) => (row.as_ptr() as *mut c_void, mem::size_of::<u64>(), b"Q\0"),
+            _ => return Err(BufferError::py_err("Row has no buffer representation")),
+        };
+
+        unsafe {
+            // The view keeps this object, and therefore the row, alive
+            let slf = (self as *const Self as *mut u8).offset(-<Self as PyTypeInfo>::OFFSET);
+            (*view).obj = slf as *mut ffi::PyObject;
+            ffi::Py_INCREF((*view).obj);
+
+            (*view).buf = buf;
+            (*view).len = self.shape * itemsize as ffi::Py_ssize_t;
+            (*view).readonly = 1;
+            (*view).itemsize = itemsize as ffi::Py_ssize_t;
+
+            (*view).format = ptr::null_mut();
+            if (flags & ffi::PyBUF_FORMAT) == ffi::PyBUF_FORMAT {
+                (*view).format = format.as_ptr() as *mut _;
+            }
+
+            (*view).ndim = 1;
+            (*view).shape = ptr::null_mut();
+            if (flags & ffi::PyBUF_ND) == ffi::PyBUF_ND {
+                (*view).shape = &self.shape as *const _ as *mut _;
+            }
+
+            (*view).strides = ptr::null_mut();
+            if (flags & ffi::PyBUF_STRIDES) == ffi::PyBUF_STRIDES {
+                (*view).strides = &((*view).itemsize) as *const _ as *mut _;
+            }
+
+            (*view).suboffsets = ptr::null_mut();
+            (*view).internal = ptr::null_mut();
+        }
+
+        Ok(())
+    }
+}
+
+#[pyfunction]
+/// Get the nth row of Pascal's triangle as a `PascalRowBuffer`
+///
+/// `dtype` may be `"u32"` (the default) or `"u64"`; entries that do not fit
+/// raise `OverflowError`.
+fn pascal_row_buffer(py: Python, n: usize, dtype: Option<&str>) -> PyResult<Py<PascalRowBuffer>> {
+    let dtype = match Dtype::from_args(false, dtype)? {
+        Dtype::U128 | Dtype::Exact => {
+            return Err(ValueError::py_err("pascal_row_buffer supports only u32 and u64"));
+        }
+        dtype => dtype,
+    };
+    let row = py.allow_threads(move || Row::compute(n, dtype))?;
 
-    list.to_object(py)
+    let shape = row.len() as ffi::Py_ssize_t;
+    Py::new(py, PascalRowBuffer { row: row, shape: shape })
 }
 
 
 #[pymodule]
 fn backend(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_wrapped(wrap_pyfunction!(pascal_row))?;
+    m.add_wrapped(wrap_pyfunction!(pascal_triangle))?;
+    m.add_wrapped(wrap_pyfunction!(pascal_row_buffer))?;
+    m.add_wrapped(wrap_pyfunction!(binomial))?;
+    m.add_wrapped(wrap_pyfunction!(pascal_row_mod))?;
+    m.add_wrapped(wrap_pyfunction!(binomial_mod))?;
+    m.add_class::<PascalTriangle>()?;
+    m.add_class::<PascalRowBuffer>()?;
 
     Ok(())
 }
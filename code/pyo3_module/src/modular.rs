@@ -0,0 +1,541 @@
+//! Binomial coefficients modulo an arbitrary modulus
+//!
+//! Prime factors of the modulus use Lucas' theorem and prime-power factors use
+//! Granville's generalisation of it; the partial results are recombined with
+//! the Chinese remainder theorem.
+
+use std::cell::RefCell;
+use std::cmp;
+use std::rc::Rc;
+
+use factorial::FactorialTable;
+use signals::{Interrupted, SignalChecker};
+
+/// Largest prime power for which factorial tables are precomputed
+const TABLE_LIMIT: u64 = 1 << 20;
+
+/// Below this, C(n, k) modulo a prime above `TABLE_LIMIT` is multiplied out
+/// rather than building a `FactorialTable`
+const DIRECT_LIMIT: u64 = 1 << 16;
+
+/// Largest block of a prime power's units that is tabulated above `TABLE_LIMIT`
+const BLOCK_LIMIT: u64 = 1 << 16;
+
+/// Number of entries of a row whose denominators are inverted together
+const ROW_CHUNK: usize = 1024;
+
+/// Number of moduli whose tables are kept by `BinomialMod::cached`
+const CACHE_SIZE: usize = 4;
+
+/// `a * b mod m`, for `a` and `b` below 2^32
+pub fn mul_mod(a: u64, b: u64, m: u64) -> u64 {
+    a * b % m
+}
+
+pub fn pow_mod(mut base: u64, mut exp: u64, m: u64) -> u64 {
+    let mut result = 1 % m;
+    base %= m;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = mul_mod(result, base, m);
+        }
+        base = mul_mod(base, base, m);
+        exp >>= 1;
+    }
+
+    result
+}
+
+/// Inverse of `a` modulo `m`, which must be coprime
+pub fn inv_mod(a: u64, m: u64) -> u64 {
+    let (mut old_r, mut r) = ((a % m) as i64, m as i64);
+    let (mut old_s, mut s) = (1i64, 0i64);
+    while r != 0 {
+        let q = old_r / r;
+        let t = old_r - q * r;
+        old_r = r;
+        r = t;
+        let t = old_s - q * s;
+        old_s = s;
+        s = t;
+    }
+
+    debug_assert!(old_r == 1, "{} is not invertible modulo {}", a, m);
+    let m = m as i64;
+    (((old_s % m) + m) % m) as u64
+}
+
+/// Replace each of `values`, which must all be coprime to `m`, by its inverse
+/// modulo `m`, with a single call to `inv_mod`
+pub fn inv_mod_all(values: &mut [u64], m: u64) {
+    let mut prefix = Vec::with_capacity(values.len());
+    let mut acc = 1 % m;
+    for &x in values.iter() {
+        prefix.push(acc);
+        acc = mul_mod(acc, x % m, m);
+    }
+
+    let mut inv = inv_mod(acc, m);
+    for (x, before) in values.iter_mut().zip(prefix).rev() {
+        let next = mul_mod(inv, *x % m, m);
+        *x = mul_mod(inv, before, m);
+        inv = next;
+    }
+}
+
+/// Split `n > 0` into p^v * u with u coprime to p, returning (v, u)
+fn split_prime(mut n: u64, p: u64) -> (u64, u64) {
+    let mut v = 0;
+    while n % p == 0 {
+        n /= p;
+        v += 1;
+    }
+
+    (v, n)
+}
+
+/// Factor `m` into `(prime, exponent)` pairs by trial division
+fn factorize(mut m: u64) -> Vec<(u64, u32)> {
+    let mut factors = Vec::new();
+
+    let mut p = 2;
+    while p * p <= m {
+        if m % p == 0 {
+            let mut e = 0;
+            while m % p == 0 {
+                m /= p;
+                e += 1;
+            }
+            factors.push((p, e));
+        }
+        p += 1;
+    }
+
+    if m > 1 {
+        factors.push((m, 1));
+    }
+
+    factors
+}
+
+/// C(n, k) modulo a prime, using Lucas' theorem
+///
+/// Primes above `TABLE_LIMIT` are not tabulated in full. Each base-p digit then
+/// costs O(sqrt(p)) multiplications with a `FactorialTable`, which is only built
+/// once a digit of k needs it.
+struct LucasTable {
+    p: u64,
+    // Both tables are empty when p exceeds `TABLE_LIMIT`
+    fact: Vec<u32>,
+    inv_fact: Vec<u32>,
+    factorials: RefCell<Option<FactorialTable>>,
+}
+
+impl LucasTable {
+    fn new(p: u64) -> LucasTable {
+        let mut fact = Vec::new();
+        let mut inv_fact = Vec::new();
+
+        if p <= TABLE_LIMIT {
+            let size = p as usize;
+            fact.reserve(size);
+            fact.push(1);
+            for i in 1..size {
+                let prev = u64::from(fact[i - 1]);
+                fact.push(mul_mod(prev, i as u64, p) as u32);
+            }
+
+            inv_fact.resize(size, 0);
+            inv_fact[size - 1] = inv_mod(u64::from(fact[size - 1]), p) as u32;
+            for i in (1..size).rev() {
+                inv_fact[i - 1] = mul_mod(u64::from(inv_fact[i]), i as u64, p) as u32;
+            }
+        }
+
+        LucasTable { p: p, fact: fact, inv_fact: inv_fact, factorials: RefCell::new(None) }
+    }
+
+    /// C(n, k) mod p for n, k < p
+    fn small_binomial(&self, n: u64, k: u64, signals: &mut SignalChecker) -> Result<u64, Interrupted> {
+        if k > n {
+            return Ok(0);
+        }
+
+        let p = self.p;
+        if !self.fact.is_empty() {
+            let (n, k) = (n as usize, k as usize);
+            let (fact, inv_fact) = (&self.fact, &self.inv_fact);
+            let value = mul_mod(u64::from(fact[n]), u64::from(inv_fact[k]), p);
+            return Ok(mul_mod(value, u64::from(inv_fact[n - k]), p));
+        }
+
+        let k = k.min(n - k);
+        if k >= DIRECT_LIMIT {
+            let mut factorials = self.factorials.borrow_mut();
+            let factorials = factorials.get_or_insert_with(|| FactorialTable::new(p));
+            let denom = mul_mod(factorials.factorial(k), factorials.factorial(n - k), p);
+            signals.tick(1)?;
+            return Ok(mul_mod(factorials.factorial(n), inv_mod(denom, p), p));
+        }
+
+        let mut num = 1;
+        let mut den = 1;
+        for i in 0..k {
+            num = mul_mod(num, n - i, p);
+            den = mul_mod(den, i + 1, p);
+            signals.tick(1)?;
+        }
+
+        Ok(mul_mod(num, inv_mod(den, p), p))
+    }
+
+    fn binomial(&self, mut n: u64, mut k: u64, signals: &mut SignalChecker) -> Result<u64, Interrupted> {
+        let p = self.p;
+        let mut result = 1 % p;
+        while k > 0 {
+            let (ni, ki) = (n % p, k % p);
+            if ki > ni {
+                return Ok(0);
+            }
+            result = mul_mod(result, self.small_binomial(ni, ki, signals)?, p);
+            n /= p;
+            k /= p;
+        }
+
+        Ok(result)
+    }
+}
+
+/// C(n, k) modulo a prime power p^e, using Granville's theorem
+///
+/// This needs the product of the units up to any r < p^e. Up to `TABLE_LIMIT`
+/// those products are simply tabulated. Above it, r is split as q * B + s for
+/// a block size B = p^b, and the product is T_{B-1}(0) T_{B-1}(B) ...
+/// T_{B-1}((q - 1) B) T_s(qB), where T_s(y) is the product of y + t over the
+/// units t <= s. Since y is a multiple of p^b, only the coefficients of T_s
+/// below y^ceil(e / b) matter modulo p^e, and those are what is tabulated.
+struct PrimePowerTable {
+    p: u64,
+    e: u64,
+    pe: u64,
+    block: u64,
+    // prod[s * terms + j] is the coefficient of y^j in T_s(y), mod p^e
+    terms: usize,
+    prod: Vec<u32>,
+    // The product of all units below p^e
+    full_block: u64,
+}
+
+impl PrimePowerTable {
+    fn new(p: u64, e: u32) -> PrimePowerTable {
+        let pe = p.pow(e);
+        let (block, terms) = if pe <= TABLE_LIMIT {
+            (pe, 1)
+        } else {
+            let mut block = p;
+            let mut b = 1;
+            while block * p <= BLOCK_LIMIT {
+                block *= p;
+                b += 1;
+            }
+            (block, ((e + b - 1) / b) as usize)
+        };
+
+        let mut prod = vec![0; block as usize * terms];
+        prod[0] = 1;
+        for s in 1..block as usize {
+            let (prev, next) = prod.split_at_mut(s * terms);
+            let prev = &prev[(s - 1) * terms..];
+            if s as u64 % p == 0 {
+                next[..terms].copy_from_slice(prev);
+                continue;
+            }
+            // Multiply T_{s-1}(y) by y + s
+            for j in 0..terms {
+                let mut c = mul_mod(u64::from(prev[j]), s as u64, pe);
+                if j > 0 {
+                    c = (c + u64::from(prev[j - 1])) % pe;
+                }
+                next[j] = c as u32;
+            }
+        }
+
+        let mut table = PrimePowerTable {
+            p: p,
+            e: u64::from(e),
+            pe: pe,
+            block: block,
+            terms: terms,
+            prod: prod,
+            full_block: 0,
+        };
+        table.full_block = table.units_product(pe - 1);
+        table
+    }
+
+    /// The product of all 1 <= j <= r coprime to p, mod p^e, for r < p^e
+    fn units_product(&self, r: u64) -> u64 {
+        let (pe, block, terms) = (self.pe, self.block, self.terms);
+        let (q, s) = (r / block, (r % block) as usize);
+        let coefficients = |s: usize| self.prod[s * terms..(s + 1) * terms].iter().map(|&c| u64::from(c));
+
+        // T_s(qB)
+        let y = mul_mod(q % pe, block % pe, pe);
+        let mut tail = 0;
+        for c in coefficients(s).rev() {
+            tail = (mul_mod(tail, y, pe) + c) % pe;
+        }
+        if q == 0 {
+            return tail;
+        }
+
+        // The product of Q(i) for i < q, where Q(x) = T_{B-1}(Bx), built up
+        // as the polynomial H(x) = Q(x) Q(x + 1) ... Q(x + count - 1). Its
+        // coefficient of x^j is a multiple of B^j, so it can be truncated the
+        // same way as T.
+        let mut block_power = 1;
+        let q_poly = coefficients(block as usize - 1)
+            .map(|c| {
+                let c = mul_mod(c, block_power, pe);
+                block_power = mul_mod(block_power, block % pe, pe);
+                c
+            })
+            .collect::<Vec<_>>();
+
+        let mut h = vec![0; terms];
+        h[0] = 1 % pe;
+        let mut count = 0;
+        for bit in (0..64 - q.leading_zeros()).rev() {
+            h = self.poly_mul(&h, &self.poly_shift(&h, count));
+            count *= 2;
+            if (q >> bit) & 1 == 1 {
+                h = self.poly_mul(&h, &self.poly_shift(&q_poly, count));
+                count += 1;
+            }
+        }
+
+        mul_mod(h[0], tail, pe)
+    }
+
+    /// a(x) * b(x), truncated to `terms` coefficients
+    fn poly_mul(&self, a: &[u64], b: &[u64]) -> Vec<u64> {
+        let pe = self.pe;
+        let mut c = vec![0; self.terms];
+        for i in 0..self.terms {
+            for j in 0..self.terms - i {
+                c[i + j] = (c[i + j] + mul_mod(a[i], b[j], pe)) % pe;
+            }
+        }
+
+        c
+    }
+
+    /// a(x + shift)
+    fn poly_shift(&self, a: &[u64], shift: u64) -> Vec<u64> {
+        let pe = self.pe;
+        let shift = shift % pe;
+        let mut c = a.to_vec();
+        // Horner's rule on the coefficients, i.e. repeated synthetic division
+        for i in 0..self.terms {
+            for j in (i..self.terms - 1).rev() {
+                c[j] = (c[j] + mul_mod(c[j + 1], shift, pe)) % pe;
+            }
+        }
+
+        c
+    }
+
+    /// Split n! into p^v * f (mod p^e) with f coprime to p, returning (f, v)
+    fn factorial(&self, mut n: u64) -> (u64, u64) {
+        let (p, pe) = (self.p, self.pe);
+
+        let mut f = 1 % pe;
+        let mut v = 0;
+        while n > 0 {
+            f = mul_mod(f, pow_mod(self.full_block, n / pe, pe), pe);
+            f = mul_mod(f, self.units_product(n % pe), pe);
+            n /= p;
+            v += n;
+        }
+
+        (f, v)
+    }
+
+    fn binomial(&self, n: u64, k: u64) -> u64 {
+        if k > n {
+            return 0;
+        }
+
+        let (fn_, vn) = self.factorial(n);
+        let (fk, vk) = self.factorial(k);
+        let (fr, vr) = self.factorial(n - k);
+
+        let v = vn - vk - vr;
+        if v >= self.e {
+            return 0;
+        }
+
+        let pe = self.pe;
+        let denom = inv_mod(mul_mod(fk, fr, pe), pe);
+        mul_mod(mul_mod(fn_, denom, pe), pow_mod(self.p, v, pe), pe)
+    }
+}
+
+enum Factor {
+    Prime(LucasTable),
+    PrimePower(PrimePowerTable),
+}
+
+impl Factor {
+    /// The factor as (p, e, p^e)
+    fn prime_power(&self) -> (u64, u64, u64) {
+        match self {
+            Factor::Prime(table) => (table.p, 1, table.p),
+            Factor::PrimePower(table) => (table.p, table.e, table.pe),
+        }
+    }
+
+    fn binomial(&self, n: u64, k: u64, signals: &mut SignalChecker) -> Result<u64, Interrupted> {
+        match self {
+            Factor::Prime(table) => table.binomial(n, k, signals),
+            Factor::PrimePower(table) => Ok(table.binomial(n, k)),
+        }
+    }
+}
+
+thread_local! {
+    // Most recently used first
+    static CACHE: RefCell<Vec<Rc<BinomialMod>>> = RefCell::new(Vec::new());
+}
+
+/// Binomial coefficients modulo a fixed `m`
+///
+/// The tables for each factor of `m` are built once, so that each call to
+/// `binomial` only costs O(log n) per factor, or O(sqrt(p)) per base-p digit
+/// for primes p too large to tabulate.
+pub struct BinomialMod {
+    m: u64,
+    factors: Vec<Factor>,
+    // The inverse of the product of the previous factors, modulo each factor
+    crt: Vec<u64>,
+}
+
+impl BinomialMod {
+    /// Build the tables for `m`, which must fit in a u32 so that products of
+    /// residues fit in a u64
+    pub fn new(m: u64) -> BinomialMod {
+        assert!(m > 0 && m <= u64::from(u32::max_value()), "modulus out of range");
+
+        let mut factors = Vec::new();
+        for (p, e) in factorize(m) {
+            if e == 1 {
+                factors.push(Factor::Prime(LucasTable::new(p)));
+            } else {
+                factors.push(Factor::PrimePower(PrimePowerTable::new(p, e)));
+            }
+        }
+
+        let mut crt = Vec::with_capacity(factors.len());
+        let mut modulus = 1;
+        for factor in &factors {
+            let (_, _, q) = factor.prime_power();
+            crt.push(inv_mod(modulus % q, q));
+            modulus *= q;
+        }
+
+        BinomialMod { m: m, factors: factors, crt: crt }
+    }
+
+    /// The tables for `m`, reused if one of the last few calls on this thread
+    /// asked for the same modulus
+    pub fn cached(m: u64) -> Rc<BinomialMod> {
+        CACHE.with(|cache| {
+            let mut cache = cache.borrow_mut();
+            let table = match cache.iter().position(|table| table.m == m) {
+                Some(i) => cache.remove(i),
+                None => Rc::new(BinomialMod::new(m)),
+            };
+            cache.insert(0, table.clone());
+            cache.truncate(CACHE_SIZE);
+            table
+        })
+    }
+
+    /// Combine residues modulo each factor with the CRT
+    fn combine(&self, residues: &[u64]) -> u64 {
+        let mut result = 0;
+        let mut modulus = 1;
+        for ((factor, &inv), &r) in self.factors.iter().zip(&self.crt).zip(residues) {
+            let (_, _, q) = factor.prime_power();
+            let diff = (r + q - result % q) % q;
+            let t = mul_mod(diff, inv, q);
+            result += modulus * t;
+            modulus *= q;
+        }
+
+        result % self.m
+    }
+
+    /// C(n, k) mod m
+    pub fn binomial(&self, n: u64, k: u64, signals: &mut SignalChecker) -> Result<u64, Interrupted> {
+        let mut residues = Vec::with_capacity(self.factors.len());
+        for factor in &self.factors {
+            residues.push(factor.binomial(n, k, signals)?);
+        }
+
+        Ok(self.combine(&residues))
+    }
+
+    /// C(n, k) mod m for k in 0..len, where len <= n + 1
+    ///
+    /// Modulo each factor p^e, C(n, k) is tracked as p^v * u with u a unit and
+    /// stepped to C(n, k + 1) by multiplying by (n - k) / (k + 1), so that each
+    /// entry costs O(log n) however large p is.
+    pub fn row(&self, n: u64, len: usize, signals: &mut SignalChecker) -> Result<Vec<u64>, Interrupted> {
+        let count = self.factors.len();
+        let mut units = vec![1; count];
+        let mut valuations = vec![0; count];
+        let mut residues = vec![0; count];
+        // For each factor, k + 1 split as p^v * u for each k in the chunk,
+        // with u already inverted
+        let mut denominators = vec![(Vec::new(), Vec::new()); count];
+
+        let mut row = Vec::new();
+        for start in (0..len).step_by(ROW_CHUNK) {
+            let end = cmp::min(start + ROW_CHUNK, len);
+            for (factor, &mut (ref mut powers, ref mut inverses)) in self.factors.iter().zip(&mut denominators) {
+                let (p, _, pe) = factor.prime_power();
+                powers.clear();
+                inverses.clear();
+                for k in start..end {
+                    let (v, u) = split_prime(k as u64 + 1, p);
+                    powers.push(v);
+                    inverses.push(u);
+                }
+                inv_mod_all(inverses, pe);
+            }
+
+            for k in start..end {
+                for (i, factor) in self.factors.iter().enumerate() {
+                    let (p, e, pe) = factor.prime_power();
+                    residues[i] = if valuations[i] >= e {
+                        0
+                    } else {
+                        mul_mod(units[i], pow_mod(p, valuations[i], pe), pe)
+                    };
+
+                    if k + 1 < len {
+                        let (a, x) = split_prime(n - k as u64, p);
+                        let (ref powers, ref inverses) = denominators[i];
+                        units[i] = mul_mod(mul_mod(units[i], x % pe, pe), inverses[k - start], pe);
+                        valuations[i] = valuations[i] + a - powers[k - start];
+                    }
+                }
+                row.push(self.combine(&residues));
+                signals.tick(count + 1)?;
+            }
+        }
+
+        Ok(row)
+    }
+}
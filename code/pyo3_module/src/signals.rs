@@ -0,0 +1,49 @@
+use pyo3::ffi;
+use pyo3::prelude::*;
+
+/// Units of work (roughly, inner loop iterations) between two signal checks
+const CHECK_INTERVAL: usize = 1 << 20;
+
+/// A Python signal handler raised while a computation was running
+///
+/// The exception itself (usually `KeyboardInterrupt`) is left pending in the
+/// interpreter and is fetched when this is converted to a `PyErr`.
+#[derive(Debug)]
+pub struct Interrupted;
+
+impl From<Interrupted> for PyErr {
+    fn from(_: Interrupted) -> PyErr {
+        let gil = Python::acquire_gil();
+        PyErr::fetch(gil.python())
+    }
+}
+
+/// Periodically runs pending Python signal handlers from a long computation
+///
+/// This works both with and without the GIL held, so it can be used inside
+/// `Python::allow_threads`: the GIL is only (re)acquired for the check itself.
+pub struct SignalChecker {
+    work: usize,
+}
+
+impl SignalChecker {
+    pub fn new() -> SignalChecker {
+        SignalChecker { work: 0 }
+    }
+
+    /// Record `work` units of work, checking for signals if enough has been done
+    pub fn tick(&mut self, work: usize) -> Result<(), Interrupted> {
+        self.work += work;
+        if self.work < CHECK_INTERVAL {
+            return Ok(());
+        }
+
+        self.work = 0;
+        let _gil = Python::acquire_gil();
+        if unsafe { ffi::PyErr_CheckSignals() } != 0 {
+            Err(Interrupted)
+        } else {
+            Ok(())
+        }
+    }
+}
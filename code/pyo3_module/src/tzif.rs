@@ -0,0 +1,734 @@
+//! Reader for the TZif files of the IANA time zone database
+//!
+//! The format is described in RFC 8536. Versions 1 to 3 are supported, as is
+//! version 4, which only changes how leap seconds may be recorded. Leap seconds
+//! are ignored, as they are by Python's `datetime`.
+//!
+//! Times after the last transition follow the POSIX TZ string in the footer of
+//! version 2 and later files, such as `EST5EDT,M3.2.0,M11.1.0`.
+//!
+//! All times here are whole seconds since 1970-01-01 00:00, either in UTC or,
+//! for wall-clock times, in local time.
+
+use std::cmp;
+use std::fs;
+use std::io;
+use std::path::{Component, Path};
+
+use date_ex::{civil_from_days, days_from_civil, MAX_YEAR, MIN_YEAR};
+
+/// Directory searched for TZif files if none is given
+pub const DEFAULT_ZONEINFO_DIR: &str = "/usr/share/zoneinfo";
+
+const SECONDS_PER_DAY: i64 = 86400;
+
+/// Default DST offset from standard time, and time of day of the changes
+const DEFAULT_DST: i64 = 3600;
+const DEFAULT_RULE_TIME: i64 = 7200;
+
+/// Largest magnitude of a UTC offset in a TZ string, and of the time of day of
+/// a change, in hours
+const MAX_OFFSET_HOURS: i64 = 24;
+const MAX_RULE_TIME_HOURS: i64 = 167;
+
+#[derive(Debug)]
+pub enum TzifError {
+    /// The key is not a relative path inside the zoneinfo directory
+    InvalidKey(String),
+    Io(io::Error),
+    /// The file is not valid TZif data
+    Malformed(String),
+}
+
+impl From<io::Error> for TzifError {
+    fn from(err: io::Error) -> TzifError {
+        TzifError::Io(err)
+    }
+}
+
+fn malformed<T, S: Into<String>>(message: S) -> Result<T, TzifError> {
+    Err(TzifError::Malformed(message.into()))
+}
+
+/// Offset from UTC and abbreviation of local time during some period
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocalTimeType {
+    /// Seconds east of UTC
+    pub utc_offset: i64,
+    /// Seconds of daylight saving time included in `utc_offset`
+    pub dst: i64,
+    pub abbreviation: String,
+}
+
+fn is_leap(year: i32) -> bool {
+    (year % 4 == 0) && (year % 100 != 0 || year % 400 == 0)
+}
+
+/// Year of the date `seconds` after 1970-01-01 00:00, clamped to years 1 to
+/// 9999
+fn year_of(seconds: i64) -> i32 {
+    let days = seconds / SECONDS_PER_DAY - if seconds % SECONDS_PER_DAY < 0 { 1 } else { 0 };
+    match civil_from_days(days) {
+        Some((year, _, _)) => year,
+        None if days < 0 => MIN_YEAR,
+        None => MAX_YEAR,
+    }
+}
+
+/// Day of the year on which a POSIX TZ rule changes the offset
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum RuleDay {
+    /// `Jn`: day `n` from 1 to 365, never counting February 29
+    Julian(i64),
+    /// `n`: day `n` from 0 to 365, counting February 29 in leap years
+    Zero(i64),
+    /// `Mm.w.d`: day `d` of the week (0 is Sunday) in week `w` from 1 to 5 of
+    /// month `m`, where week 5 means the last such day of the month
+    Month { month: u8, week: u8, weekday: u8 },
+}
+
+impl RuleDay {
+    /// Days from 1970-01-01 to this day in `year`
+    fn days(self, year: i32) -> i64 {
+        match self {
+            RuleDay::Julian(n) => {
+                let days = days_from_civil(year, 1, 1) + n - 1;
+                if n >= 60 && is_leap(year) {
+                    days + 1
+                } else {
+                    days
+                }
+            }
+            RuleDay::Zero(n) => days_from_civil(year, 1, 1) + n,
+            RuleDay::Month {
+                month,
+                week,
+                weekday,
+            } => {
+                let first = days_from_civil(year, month, 1);
+                let next = if month == 12 {
+                    days_from_civil(year + 1, 1, 1)
+                } else {
+                    days_from_civil(year, month + 1, 1)
+                };
+
+                // 1970-01-01 was a Thursday
+                let first_weekday = (first % 7 + 11) % 7;
+                let mut day = first
+                    + (i64::from(weekday) - first_weekday + 7) % 7
+                    + 7 * (i64::from(week) - 1);
+                if day >= next {
+                    day -= 7;
+                }
+
+                day
+            }
+        }
+    }
+}
+
+/// A change between standard and daylight saving time in a POSIX TZ rule
+#[derive(Clone, Debug, PartialEq)]
+struct RuleChange {
+    day: RuleDay,
+    /// Seconds after local midnight, in the time in effect before the change
+    time: i64,
+}
+
+impl RuleChange {
+    /// UTC time of the change in `year`, from local time at `utc_offset`
+    fn at(&self, year: i32, utc_offset: i64) -> i64 {
+        self.day.days(year) * SECONDS_PER_DAY + self.time - utc_offset
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct DstRule {
+    dst: LocalTimeType,
+    start: RuleChange,
+    end: RuleChange,
+}
+
+/// Local time given by a POSIX TZ string
+#[derive(Clone, Debug, PartialEq)]
+struct Rule {
+    std: LocalTimeType,
+    dst: Option<DstRule>,
+}
+
+impl Rule {
+    /// Whether `t` is in DST, given the start and end of DST in its year
+    fn in_dst(t: i64, start: i64, end: i64) -> bool {
+        if start < end {
+            start <= t && t < end
+        } else {
+            // DST spans the new year, as in the southern hemisphere
+            !(end <= t && t < start)
+        }
+    }
+
+    /// Local time type at the UTC time `t`, and whether the local time is the
+    /// second of two with the same wall-clock time
+    fn find_utc(&self, t: i64) -> (&LocalTimeType, bool) {
+        let rule = match self.dst {
+            Some(ref rule) => rule,
+            None => return (&self.std, false),
+        };
+
+        let year = year_of(t);
+        let start = rule.start.at(year, self.std.utc_offset);
+        let end = rule.end.at(year, rule.dst.utc_offset);
+        let ty = if Rule::in_dst(t, start, end) {
+            &rule.dst
+        } else {
+            &self.std
+        };
+
+        // Wall-clock times repeat after a change to a smaller offset: the end
+        // of DST, or its start if DST is negative
+        let repeated = rule.dst.utc_offset - self.std.utc_offset;
+        let fold = if repeated > 0 {
+            end <= t && t < end + repeated
+        } else {
+            start <= t && t < start - repeated
+        };
+
+        (ty, fold)
+    }
+
+    /// Local time type at the wall-clock time `wall`, taking the earlier of
+    /// two possible times unless `fold` is set
+    fn find_local(&self, wall: i64, fold: bool) -> &LocalTimeType {
+        let rule = match self.dst {
+            Some(ref rule) => rule,
+            None => return &self.std,
+        };
+
+        let year = year_of(wall);
+        let offsets = (self.std.utc_offset, rule.dst.utc_offset);
+        let shift = if fold {
+            cmp::min(offsets.0, offsets.1)
+        } else {
+            cmp::max(offsets.0, offsets.1)
+        };
+        let start = rule.start.at(year, self.std.utc_offset) + shift;
+        let end = rule.end.at(year, rule.dst.utc_offset) + shift;
+
+        if Rule::in_dst(wall, start, end) {
+            &rule.dst
+        } else {
+            &self.std
+        }
+    }
+}
+
+/// Parser for the POSIX TZ strings in TZif footers
+struct RuleParser<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> RuleParser<'a> {
+    fn parse(text: &str) -> Result<Rule, TzifError> {
+        let mut parser = RuleParser {
+            text: text.as_bytes(),
+            pos: 0,
+        };
+        match parser.rule() {
+            Ok(rule) if parser.pos == parser.text.len() => Ok(rule),
+            _ => malformed(format!("invalid TZ string {:?}", text)),
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.pos).cloned()
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        let found = self.peek() == Some(byte);
+        if found {
+            self.pos += 1;
+        }
+
+        found
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), TzifError> {
+        if self.eat(byte) {
+            Ok(())
+        } else {
+            malformed("unexpected character")
+        }
+    }
+
+    fn rule(&mut self) -> Result<Rule, TzifError> {
+        let std = LocalTimeType {
+            abbreviation: self.abbreviation()?,
+            utc_offset: self.offset()?,
+            dst: 0,
+        };
+        if self.peek().is_none() {
+            return Ok(Rule { std, dst: None });
+        }
+
+        let abbreviation = self.abbreviation()?;
+        let utc_offset = match self.peek() {
+            Some(b',') => std.utc_offset + DEFAULT_DST,
+            _ => self.offset()?,
+        };
+        let dst = LocalTimeType {
+            abbreviation,
+            utc_offset,
+            dst: utc_offset - std.utc_offset,
+        };
+
+        self.expect(b',')?;
+        let start = self.change()?;
+        self.expect(b',')?;
+        let end = self.change()?;
+
+        Ok(Rule {
+            std,
+            dst: Some(DstRule { dst, start, end }),
+        })
+    }
+
+    /// An abbreviation of three or more letters, or `<...>` quoting any
+    /// letters, digits, `+` and `-`
+    fn abbreviation(&mut self) -> Result<String, TzifError> {
+        let all = self.text;
+        let quoted = self.eat(b'<');
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            let valid = if quoted {
+                c.is_ascii_alphanumeric() || c == b'+' || c == b'-'
+            } else {
+                c.is_ascii_alphabetic()
+            };
+            if !valid {
+                break;
+            }
+            self.pos += 1;
+        }
+
+        let text = &all[start..self.pos];
+        if quoted {
+            self.expect(b'>')?;
+        }
+
+        if text.len() < 3 {
+            return malformed("abbreviation too short");
+        }
+
+        Ok(String::from_utf8_lossy(text).into_owned())
+    }
+
+    fn number(&mut self, max_digits: usize) -> Result<i64, TzifError> {
+        let start = self.pos;
+        let mut value = 0;
+        while let Some(digit) = self.peek().filter(u8::is_ascii_digit) {
+            if self.pos - start == max_digits {
+                return malformed("number too long");
+            }
+            value = value * 10 + i64::from(digit - b'0');
+            self.pos += 1;
+        }
+
+        if self.pos == start {
+            malformed("expected a number")
+        } else {
+            Ok(value)
+        }
+    }
+
+    /// `[+-]hh[:mm[:ss]]` in seconds, with hours up to `max_hours`
+    fn duration(&mut self, max_hours: i64) -> Result<i64, TzifError> {
+        let sign = if self.eat(b'-') {
+            -1
+        } else {
+            self.eat(b'+');
+            1
+        };
+
+        let hours = self.number(3)?;
+        let mut seconds = hours * 3600;
+        if self.eat(b':') {
+            seconds += self.number(2)? * 60;
+            if self.eat(b':') {
+                seconds += self.number(2)?;
+            }
+        }
+
+        if hours > max_hours {
+            return malformed("hours out of range");
+        }
+
+        Ok(sign * seconds)
+    }
+
+    /// UTC offset, which TZ strings give as the time west of UTC
+    fn offset(&mut self) -> Result<i64, TzifError> {
+        Ok(-self.duration(MAX_OFFSET_HOURS)?)
+    }
+
+    fn change(&mut self) -> Result<RuleChange, TzifError> {
+        let day = if self.eat(b'M') {
+            let month = self.number(2)?;
+            self.expect(b'.')?;
+            let week = self.number(1)?;
+            self.expect(b'.')?;
+            let weekday = self.number(1)?;
+            if month < 1 || month > 12 || week < 1 || week > 5 || weekday > 6 {
+                return malformed("invalid month rule");
+            }
+
+            RuleDay::Month {
+                month: month as u8,
+                week: week as u8,
+                weekday: weekday as u8,
+            }
+        } else if self.eat(b'J') {
+            let day = self.number(3)?;
+            if day < 1 || day > 365 {
+                return malformed("Julian day out of range");
+            }
+
+            RuleDay::Julian(day)
+        } else {
+            let day = self.number(3)?;
+            if day > 365 {
+                return malformed("day of year out of range");
+            }
+
+            RuleDay::Zero(day)
+        };
+
+        let time = if self.eat(b'/') {
+            self.duration(MAX_RULE_TIME_HOURS)?
+        } else {
+            DEFAULT_RULE_TIME
+        };
+
+        Ok(RuleChange { day, time })
+    }
+}
+
+/// Cursor over the bytes of a TZif file
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], TzifError> {
+        if self.data.len() - self.pos < len {
+            return malformed("unexpected end of file");
+        }
+
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    /// Big-endian signed integer of `size` bytes
+    fn int(&mut self, size: usize) -> Result<i64, TzifError> {
+        let bytes = self.bytes(size)?;
+        let value = bytes
+            .iter()
+            .fold(0u64, |value, &byte| value << 8 | u64::from(byte));
+
+        // Sign-extend from the top bit of the last byte
+        let unused = 64 - 8 * size as u32;
+        Ok(((value << unused) as i64) >> unused)
+    }
+
+    fn count(&mut self) -> Result<usize, TzifError> {
+        Ok(self.int(4)? as u32 as usize)
+    }
+}
+
+/// Counts from a TZif header, giving the sizes of the data block after it
+struct Header {
+    version: u8,
+    isutcnt: usize,
+    isstdcnt: usize,
+    leapcnt: usize,
+    timecnt: usize,
+    typecnt: usize,
+    charcnt: usize,
+}
+
+impl Header {
+    fn read(reader: &mut Reader) -> Result<Header, TzifError> {
+        match reader.bytes(4) {
+            Ok(magic) if magic == b"TZif" => {}
+            _ => return malformed("not a TZif file"),
+        }
+
+        let version = match reader.bytes(1)?[0] {
+            0 => 1,
+            b'2' => 2,
+            b'3' => 3,
+            b'4' => 4,
+            other => return malformed(format!("unsupported TZif version {:?}", other as char)),
+        };
+        reader.bytes(15)?;
+
+        let header = Header {
+            version,
+            isutcnt: reader.count()?,
+            isstdcnt: reader.count()?,
+            leapcnt: reader.count()?,
+            timecnt: reader.count()?,
+            typecnt: reader.count()?,
+            charcnt: reader.count()?,
+        };
+
+        if header.typecnt == 0 || header.charcnt == 0 {
+            return malformed("no local time types");
+        } else if (header.isutcnt != 0 && header.isutcnt != header.typecnt)
+            || (header.isstdcnt != 0 && header.isstdcnt != header.typecnt)
+        {
+            return malformed("inconsistent counts");
+        }
+
+        Ok(header)
+    }
+
+    /// Length of the data block, with transition times of `time_size` bytes
+    fn data_len(&self, time_size: usize) -> usize {
+        self.timecnt * (time_size + 1)
+            + self.typecnt * 6
+            + self.charcnt
+            + self.leapcnt * (time_size + 4)
+            + self.isstdcnt
+            + self.isutcnt
+    }
+}
+
+/// Local time of a TZif file
+#[derive(Clone, Debug)]
+pub struct TimeZone {
+    /// UTC times of the transitions, in increasing order
+    transitions: Vec<i64>,
+    /// Local time type from each transition until the next
+    types: Vec<LocalTimeType>,
+    /// Local time type before the first transition
+    initial: LocalTimeType,
+    /// Rule for times after the last transition
+    rule: Option<Rule>,
+}
+
+impl TimeZone {
+    /// Read the time zone `key`, such as `"Europe/London"`, from `dir`
+    pub fn load(dir: &Path, key: &str) -> Result<TimeZone, TzifError> {
+        let path = Path::new(key);
+        let relative = path.components().all(|part| match part {
+            Component::Normal(_) => true,
+            _ => false,
+        });
+        if key.is_empty() || !relative {
+            return Err(TzifError::InvalidKey(key.to_string()));
+        }
+
+        TimeZone::parse(&fs::read(dir.join(path))?)
+    }
+
+    pub fn parse(data: &[u8]) -> Result<TimeZone, TzifError> {
+        let mut reader = Reader { data, pos: 0 };
+        let header = Header::read(&mut reader)?;
+        if header.version == 1 {
+            return TimeZone::read_data(&mut reader, &header, 4);
+        }
+
+        // Version 2 and later repeat the data with 64-bit times, then add the
+        // footer
+        reader.bytes(header.data_len(4))?;
+        let header = Header::read(&mut reader)?;
+        let mut zone = TimeZone::read_data(&mut reader, &header, 8)?;
+
+        if reader.bytes(1)? != b"\n" {
+            return malformed("missing footer");
+        }
+        let footer = &reader.data[reader.pos..];
+        let footer = match footer.iter().position(|&byte| byte == b'\n') {
+            Some(end) => &footer[..end],
+            None => return malformed("unterminated footer"),
+        };
+        if !footer.is_empty() {
+            let footer = String::from_utf8_lossy(footer);
+            zone.rule = Some(RuleParser::parse(&footer)?);
+        }
+
+        Ok(zone)
+    }
+
+    fn read_data(
+        reader: &mut Reader,
+        header: &Header,
+        time_size: usize,
+    ) -> Result<TimeZone, TzifError> {
+        let mut transitions = Vec::with_capacity(header.timecnt);
+        for _ in 0..header.timecnt {
+            transitions.push(reader.int(time_size)?);
+        }
+        if transitions.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return malformed("transition times out of order");
+        }
+
+        let indices = reader.bytes(header.timecnt)?;
+        if indices
+            .iter()
+            .any(|&index| usize::from(index) >= header.typecnt)
+        {
+            return malformed("invalid local time type index");
+        }
+
+        let mut raw_types = Vec::with_capacity(header.typecnt);
+        for _ in 0..header.typecnt {
+            let utc_offset = reader.int(4)?;
+            let is_dst = reader.bytes(1)?[0] != 0;
+            let index = usize::from(reader.bytes(1)?[0]);
+            raw_types.push((utc_offset, is_dst, index));
+        }
+
+        let chars = reader.bytes(header.charcnt)?;
+        reader.bytes(header.leapcnt * (time_size + 4) + header.isstdcnt + header.isutcnt)?;
+
+        let mut types = Vec::with_capacity(header.typecnt);
+        for &(utc_offset, is_dst, index) in &raw_types {
+            if utc_offset == i64::from(i32::min_value()) {
+                return malformed("invalid UTC offset");
+            }
+            let abbreviation = match chars.get(index..).and_then(|rest| {
+                rest.iter()
+                    .position(|&byte| byte == 0)
+                    .map(|end| &rest[..end])
+            }) {
+                Some(abbreviation) => String::from_utf8_lossy(abbreviation).into_owned(),
+                None => return malformed("invalid abbreviation index"),
+            };
+
+            types.push(LocalTimeType {
+                utc_offset,
+                dst: if is_dst { DEFAULT_DST } else { 0 },
+                abbreviation,
+            });
+        }
+
+        // DST types only record their total offset, so the DST part is
+        // inferred from the standard time before or after them
+        let is_dst: Vec<bool> = raw_types.iter().map(|&(_, is_dst, _)| is_dst).collect();
+        let mut inferred = vec![false; types.len()];
+        for (i, &index) in indices.iter().enumerate() {
+            let index = usize::from(index);
+            if !is_dst[index] || inferred[index] {
+                continue;
+            }
+
+            let neighbours = [i.checked_sub(1), Some(i + 1)];
+            let dst = neighbours
+                .iter()
+                .filter_map(|&j| j.and_then(|j| indices.get(j)))
+                .map(|&j| usize::from(j))
+                .filter(|&j| !is_dst[j])
+                .map(|j| types[index].utc_offset - types[j].utc_offset)
+                .find(|&dst| dst != 0);
+            if let Some(dst) = dst {
+                types[index].dst = dst;
+                inferred[index] = true;
+            }
+        }
+
+        Ok(TimeZone {
+            transitions,
+            initial: types[0].clone(),
+            types: indices
+                .iter()
+                .map(|&index| types[usize::from(index)].clone())
+                .collect(),
+            rule: None,
+        })
+    }
+
+    /// Local time type after the first `count` transitions
+    fn after(&self, count: usize) -> &LocalTimeType {
+        match count {
+            0 => &self.initial,
+            _ => &self.types[count - 1],
+        }
+    }
+
+    /// Number of transitions at or before the wall-clock time `wall`
+    ///
+    /// Around each transition, wall-clock times between the old and new
+    /// offsets are either skipped or repeated. With `fold` unset, they are
+    /// taken to be before the transition, and otherwise after it.
+    fn count_local(&self, wall: i64, fold: bool) -> usize {
+        let (mut low, mut high) = (0, self.transitions.len());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let offsets = (self.after(mid).utc_offset, self.after(mid + 1).utc_offset);
+            let shift = if fold {
+                cmp::min(offsets.0, offsets.1)
+            } else {
+                cmp::max(offsets.0, offsets.1)
+            };
+
+            if self.transitions[mid] + shift <= wall {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+
+        low
+    }
+
+    /// Local time type at the UTC time `t`, and whether the local time is the
+    /// second of two with the same wall-clock time
+    pub fn find_utc(&self, t: i64) -> (&LocalTimeType, bool) {
+        let count = match self.transitions.binary_search(&t) {
+            Ok(i) => i + 1,
+            Err(i) => i,
+        };
+
+        let (ty, fold) = match self.rule {
+            // The rule takes over strictly after the last transition
+            Some(ref rule)
+                if count == self.transitions.len() && self.transitions.last() != Some(&t) =>
+            {
+                rule.find_utc(t)
+            }
+            _ => (self.after(count), false),
+        };
+        if count == 0 {
+            return (ty, fold);
+        }
+
+        // Wall-clock times repeat after a transition to a smaller offset
+        let repeated = self.after(count - 1).utc_offset - self.after(count).utc_offset;
+        (ty, fold || t - self.transitions[count - 1] < repeated)
+    }
+
+    /// Local time type at the wall-clock time `wall`, taking the earlier of
+    /// two possible times unless `fold` is set
+    pub fn find_local(&self, wall: i64, fold: bool) -> &LocalTimeType {
+        let count = self.count_local(wall, fold);
+        match self.rule {
+            Some(ref rule) if count == self.transitions.len() => rule.find_local(wall, fold),
+            _ => self.after(count),
+        }
+    }
+
+    /// Whether the zone has only ever had one local time type, so that its
+    /// offset does not depend on the time
+    pub fn is_fixed(&self) -> bool {
+        let fixed_rule = match self.rule {
+            Some(ref rule) => rule.dst.is_none() && rule.std == self.initial,
+            None => true,
+        };
+
+        fixed_rule && self.types.iter().all(|ty| *ty == self.initial)
+    }
+}
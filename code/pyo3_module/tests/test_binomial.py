@@ -0,0 +1,84 @@
+import math
+
+import pytest
+
+from pomodule import backend
+
+DTYPE_BITS = {'u32': 32, 'u64': 64, 'u128': 128}
+
+
+@pytest.mark.parametrize('dtype', ['u32', 'u64', 'u128'])
+def test_binomial_every_small_entry(dtype):
+    # Covers every entry up to a few rows past the first that overflows
+    for n in range(140):
+        for k in range(n + 3):
+            expected = math.comb(n, k)
+            if expected < 2 ** DTYPE_BITS[dtype]:
+                assert backend.binomial(n, k, dtype=dtype) == expected, (n, k)
+            else:
+                with pytest.raises(OverflowError):
+                    backend.binomial(n, k, dtype=dtype)
+
+
+@pytest.mark.parametrize('dtype,fits,overflows', [
+    ('u32', (34, 17), (35, 17)),
+    ('u64', (67, 33), (68, 34)),
+    ('u128', (131, 65), (132, 66)),
+])
+def test_binomial_overflow_boundary(dtype, fits, overflows):
+    assert backend.binomial(*fits, dtype=dtype) == math.comb(*fits)
+
+    message = r'C\({}, {}\) does not fit in {}'.format(*overflows, dtype)
+    with pytest.raises(OverflowError, match=message):
+        backend.binomial(*overflows, dtype=dtype)
+
+
+def test_binomial_default_dtype_is_u32():
+    assert backend.binomial(34, 17) == math.comb(34, 17)
+    with pytest.raises(OverflowError, match='u32'):
+        backend.binomial(35, 17)
+
+
+@pytest.mark.parametrize('dtype,n,k', [
+    ('u32', 2 ** 32 - 1, 1),
+    ('u32', 2 ** 32 - 1, 2 ** 32 - 2),
+    ('u32', 2 ** 40, 2 ** 40),
+    ('u64', 2 ** 64 - 1, 1),
+    ('u128', 2 ** 64 - 1, 2),
+    ('u128', 2 ** 42 + 1, 3),
+])
+def test_binomial_large_n(dtype, n, k):
+    assert backend.binomial(n, k, dtype=dtype) == math.comb(n, k)
+
+
+@pytest.mark.parametrize('dtype,n,k', [
+    ('u32', 2 ** 32, 1),
+    ('u64', 2 ** 64 - 1, 2),
+])
+def test_binomial_large_n_overflow(dtype, n, k):
+    with pytest.raises(OverflowError):
+        backend.binomial(n, k, dtype=dtype)
+
+
+@pytest.mark.parametrize('n,k', [
+    (0, 0),
+    (1000, 1),
+    (1000, 500),
+    (4097, 1234),
+    (10000, 5000),
+    (10001, 9000),
+    (2 ** 64 - 1, 5),
+])
+def test_binomial_exact(n, k):
+    assert backend.binomial(n, k, exact=True) == math.comb(n, k)
+
+
+@pytest.mark.parametrize('kwargs', [
+    {},
+    {'dtype': 'u64'},
+    {'dtype': 'u128'},
+    {'exact': True},
+])
+@pytest.mark.parametrize('n,k', [(0, 1), (5, 6), (10, 2 ** 63)])
+def test_binomial_k_greater_than_n(kwargs, n, k):
+    assert backend.binomial(n, k, **kwargs) == 0
@@ -0,0 +1,83 @@
+import math
+
+import pytest
+
+from pomodule import backend
+
+MODULI = [
+    # Primes, including ones too large to tabulate factorials for
+    2, 3, 5, 7, 13, 97, 1048583, 1000000007, 2 ** 32 - 5,
+    # Powers of 2, whose units multiply to 1 rather than -1 from 2**3 on
+    4, 8, 16, 1024, 2 ** 20, 2 ** 21, 2 ** 31,
+    # Odd prime powers, including ones too large to tabulate in full
+    9, 25, 27, 49, 125, 3 ** 12, 3 ** 13, 3 ** 20, 2053 ** 2, 65521 ** 2,
+    # Composites, combined with the CRT
+    6, 12, 100, 2 * 3 * 5 * 7, 720, 2 * 3 * 5 * 7 * 11 * 13 * 17 * 19,
+    2 * 1000003, 4 * 9 * 25 * 49, 5 * 2 ** 21, 2 ** 32 - 1,
+    1,
+]
+
+
+@pytest.mark.parametrize('m', MODULI)
+def test_pascal_row_mod_small_rows(m):
+    for n in range(1, 80):
+        expected = [math.comb(n - 1, k) % m for k in range(n)]
+        assert backend.pascal_row_mod(n, m) == expected, n
+
+
+@pytest.mark.parametrize('m', MODULI)
+@pytest.mark.parametrize('n,k', [
+    (100, 50),
+    (1000, 333),
+    (12345, 6789),
+    (3 ** 13, 1000),
+    (2 ** 40 + 12345, 77),
+    (10 ** 18, 3),
+    (2 ** 64 - 1, 2),
+])
+def test_binomial_mod(m, n, k):
+    assert backend.binomial_mod(n, k, m) == math.comb(n, k) % m
+
+
+@pytest.mark.parametrize('m', [1, 2, 8, 9, 12, 97])
+@pytest.mark.parametrize('n,k', [(0, 1), (5, 6), (100, 2 ** 64 - 1)])
+def test_binomial_mod_k_greater_than_n(m, n, k):
+    assert backend.binomial_mod(n, k, m) == 0
+
+
+def _binomial_mod_prime(n, k, p):
+    """C(n, k) mod p by Lucas' theorem, multiplying out each digit"""
+    result = 1
+    while k:
+        (n, ni), (k, ki) = divmod(n, p), divmod(k, p)
+        for i in range(ki):
+            result = result * (ni - i) * pow(i + 1, -1, p) % p
+    return result
+
+
+@pytest.mark.parametrize('p', [1048583, 1000000007, 2 ** 32 - 5])
+@pytest.mark.parametrize('n,k', [
+    (987654, 123456),
+    (987654, 864198),
+    (10 ** 19, 200000),
+])
+def test_binomial_mod_large_digits(p, n, k):
+    # Digits of k from 2**16 on use the sampled factorials of a FactorialTable
+    # instead of being multiplied out, including through Wilson's theorem when
+    # a digit of n is above p / 2
+    assert backend.binomial_mod(n, k, p) == _binomial_mod_prime(n, k, p)
+
+
+def test_binomial_mod_half_prime_digits():
+    p = 1000000007
+    n = 10 ** 18 // p * p + p - 1
+    # C(p - 1, k) = (-1)^k mod p
+    assert backend.binomial_mod(n, 500000003, p) == p - 1
+    assert backend.binomial_mod(n, 500000004, p) == 1
+
+
+def test_zero_modulus():
+    with pytest.raises(ValueError):
+        backend.binomial_mod(10, 5, 0)
+    with pytest.raises(ValueError):
+        backend.pascal_row_mod(10, 0)
@@ -0,0 +1,442 @@
+import datetime
+import os
+
+import pytest
+
+from pomodule import date_ex
+
+SECONDS_PER_DAY = 86400
+
+ZONEINFO_DIR = os.path.join(os.path.dirname(__file__), 'fixtures', 'zoneinfo')
+
+
+def test_days_from_civil_every_date():
+    # seconds_before converts d to days with days_from_civil and back with
+    # civil_from_days, so landing exactly on 0001-01-01 checks the day count
+    # of every date against toordinal
+    first = datetime.date.min
+    for ordinal in range(first.toordinal(), datetime.date.max.toordinal() + 1):
+        d = datetime.date.fromordinal(ordinal)
+        seconds = (ordinal - first.toordinal()) * SECONDS_PER_DAY
+        assert date_ex.seconds_before(d, seconds) == first, d
+
+
+def test_civil_from_days_every_date():
+    first = datetime.date.min
+    for ordinal in range(first.toordinal(), datetime.date.max.toordinal() + 1):
+        seconds = (first.toordinal() - ordinal) * SECONDS_PER_DAY
+        assert (date_ex.seconds_before(first, seconds)
+                == datetime.date.fromordinal(ordinal)), ordinal
+
+
+@pytest.mark.parametrize('d,seconds,expected', [
+    (datetime.date(2020, 3, 1), 1, datetime.date(2020, 2, 29)),
+    (datetime.date(2000, 3, 1), SECONDS_PER_DAY, datetime.date(2000, 2, 29)),
+    (datetime.date(1900, 3, 1), SECONDS_PER_DAY, datetime.date(1900, 2, 28)),
+    (datetime.date(1970, 1, 1), -SECONDS_PER_DAY + 1, datetime.date(1970, 1, 1)),
+    (datetime.date(1970, 1, 1), 0, datetime.date(1970, 1, 1)),
+])
+def test_seconds_before(d, seconds, expected):
+    assert date_ex.seconds_before(d, seconds) == expected
+
+
+@pytest.mark.parametrize('dt', [
+    datetime.datetime(1970, 1, 1),
+    datetime.datetime(2020, 2, 29, 23, 59, 59, 999999),
+    datetime.datetime(1, 1, 1, 0, 0, 1, 1),
+    datetime.datetime(9999, 12, 31, 12, 30, 15, 500000),
+])
+@pytest.mark.parametrize('seconds', [
+    0, 1, -1, 59, 3600, -86399, 86400 * 366, -86400 * 365 * 400,
+])
+def test_seconds_before_datetime(dt, seconds):
+    try:
+        expected = dt - datetime.timedelta(seconds=seconds)
+    except OverflowError:
+        with pytest.raises(OverflowError):
+            date_ex.seconds_before(dt, seconds)
+    else:
+        result = date_ex.seconds_before(dt, seconds)
+        assert type(result) is datetime.datetime
+        assert result == expected
+
+
+def test_seconds_before_keeps_tzinfo_and_fold():
+    tz = datetime.timezone(datetime.timedelta(hours=-5), 'EST')
+    dt = datetime.datetime(2021, 11, 7, 1, 30, 0, 250, tzinfo=tz, fold=1)
+    result = date_ex.seconds_before(dt, 90)
+    assert result.tzinfo is tz
+    assert result.fold == 1
+    assert result == datetime.datetime(2021, 11, 7, 1, 28, 30, 250, tzinfo=tz)
+
+
+def test_seconds_before_date_stays_date():
+    result = date_ex.seconds_before(datetime.date(2020, 1, 1), 1)
+    assert type(result) is datetime.date
+    assert result == datetime.date(2019, 12, 31)
+
+
+@pytest.mark.parametrize('value', [None, 0, '2020-01-01', datetime.time(12)])
+def test_seconds_before_wrong_type(value):
+    with pytest.raises(TypeError):
+        date_ex.seconds_before(value, 0)
+
+
+@pytest.mark.parametrize('d,seconds', [
+    (datetime.datetime.min, 1),
+    (datetime.datetime.max, -1),
+    (datetime.datetime(2000, 1, 1), 2 ** 62),
+    (datetime.date.min, 1),
+    (datetime.date.max, -SECONDS_PER_DAY),
+    (datetime.date.max, -2 ** 63),
+])
+def test_seconds_before_out_of_range(d, seconds):
+    with pytest.raises(OverflowError):
+        date_ex.seconds_before(d, seconds)
+
+
+@pytest.mark.parametrize('d', [
+    datetime.date(2020, 2, 29),
+    datetime.datetime(2020, 2, 29, 23, 59, 59, 999999),
+    datetime.datetime(1969, 12, 31, 0, 0, 0, 1,
+                      tzinfo=datetime.timezone.utc),
+])
+@pytest.mark.parametrize('delta', [
+    datetime.timedelta(0),
+    datetime.timedelta(microseconds=1),
+    datetime.timedelta(microseconds=-1),
+    datetime.timedelta(days=1, seconds=3599, microseconds=999999),
+    datetime.timedelta(days=-400, hours=-5),
+])
+def test_timedelta_offsets(d, delta):
+    if type(d) is datetime.datetime:
+        assert date_ex.seconds_before(d, delta) == d - delta
+        assert date_ex.seconds_after(d, delta) == d + delta
+        assert date_ex.shift(d, delta) == d + delta
+    else:
+        # Dates are midnight at their start, so sub-day parts still count
+        midnight = datetime.datetime.combine(d, datetime.time())
+        assert date_ex.seconds_before(d, delta) == (midnight - delta).date()
+        assert date_ex.seconds_after(d, delta) == (midnight + delta).date()
+        assert date_ex.shift(d, delta) == (midnight + delta).date()
+
+
+def test_int_and_timedelta_offsets_agree():
+    d = datetime.datetime(2000, 1, 1, 12)
+    for seconds in [0, 1, -1, 86400, 123456789]:
+        delta = datetime.timedelta(seconds=seconds)
+        assert date_ex.seconds_after(d, seconds) == date_ex.shift(d, delta)
+        assert (date_ex.seconds_before(d, seconds)
+                == date_ex.seconds_before(d, delta))
+
+
+@pytest.mark.parametrize('func,d,offset,match', [
+    (date_ex.seconds_before, datetime.date.min, 1, 'before 0001-01-01'),
+    (date_ex.seconds_after, datetime.date.max, 86400, 'after 9999-12-31'),
+    (date_ex.shift, datetime.datetime.min, -datetime.timedelta.resolution,
+     'before 0001-01-01'),
+    (date_ex.shift, datetime.date(2000, 1, 1), datetime.timedelta.max,
+     'after 9999-12-31'),
+    (date_ex.seconds_before, datetime.datetime(2000, 1, 1),
+     datetime.timedelta.min, 'after 9999-12-31'),
+])
+def test_out_of_range_message(func, d, offset, match):
+    with pytest.raises(OverflowError, match=match):
+        func(d, offset)
+
+
+@pytest.mark.parametrize('offset', [1.5, '1', None])
+def test_wrong_offset_type(offset):
+    with pytest.raises(TypeError):
+        date_ex.seconds_before(datetime.date(2000, 1, 1), offset)
+    with pytest.raises(TypeError):
+        date_ex.shift(datetime.date(2000, 1, 1), offset)
+
+
+@pytest.mark.parametrize('offset,name', [
+    (0, 'UTC'),
+    (19800, 'UTC+05:30'),
+    (-18000, 'UTC-05:00'),
+    (datetime.timedelta(hours=-9, minutes=-30, seconds=-15), 'UTC-09:30:15'),
+    (datetime.timedelta(seconds=1, microseconds=5), 'UTC+00:00:01.000005'),
+])
+def test_fixed_offset_matches_timezone(offset, name):
+    tz = date_ex.FixedOffset(offset)
+    if not isinstance(offset, datetime.timedelta):
+        offset = datetime.timedelta(seconds=offset)
+    expected = datetime.timezone(offset)
+    dt = datetime.datetime(2020, 6, 1, 12, tzinfo=tz)
+
+    assert isinstance(tz, datetime.tzinfo)
+    assert tz.utcoffset(dt) == expected.utcoffset(dt) == offset
+    assert tz.utcoffset(None) == offset
+    assert tz.dst(dt) is None
+    assert tz.tzname(dt) == expected.tzname(dt) == name
+    assert dt == datetime.datetime(2020, 6, 1, 12, tzinfo=expected)
+    assert dt.tzname() == name
+
+
+def test_fixed_offset_name():
+    tz = date_ex.FixedOffset(datetime.timedelta(hours=10), 'AEST')
+    assert datetime.datetime(2020, 1, 1, tzinfo=tz).tzname() == 'AEST'
+
+
+@pytest.mark.parametrize('offset', [
+    86400, -86400, datetime.timedelta(days=1), datetime.timedelta(days=-2),
+])
+def test_fixed_offset_out_of_range(offset):
+    with pytest.raises(ValueError):
+        date_ex.FixedOffset(offset)
+
+
+def test_fixed_offset_astimezone():
+    tz = date_ex.FixedOffset(-3600 * 7)
+    utc = datetime.datetime(2000, 1, 1, 3, 15, 0, 7, tzinfo=datetime.timezone.utc)
+    local = utc.astimezone(tz)
+    assert local.tzinfo is tz
+    assert local.replace(tzinfo=None) == datetime.datetime(1999, 12, 31, 20, 15, 0, 7)
+    assert local == utc
+
+
+def test_fixed_offset_fromutc_other_tzinfo():
+    # Like tzinfo.fromutc, dt must already have been given this tzinfo
+    tz = date_ex.FixedOffset(3600)
+    other = date_ex.FixedOffset(3600)
+    for dt in [
+        datetime.datetime(2020, 1, 1),
+        datetime.datetime(2020, 1, 1, tzinfo=other),
+        datetime.datetime(2020, 1, 1, tzinfo=datetime.timezone.utc),
+    ]:
+        with pytest.raises(ValueError, match='is not self'):
+            tz.fromutc(dt)
+
+    local = tz.fromutc(datetime.datetime(2020, 1, 1, tzinfo=tz))
+    assert local.tzinfo is tz
+    assert local.replace(tzinfo=None) == datetime.datetime(2020, 1, 1, 1)
+
+
+@pytest.mark.parametrize('d', [
+    datetime.date(1970, 1, 1),
+    datetime.date(1, 1, 1),
+    datetime.datetime(2021, 3, 14, 1, 59, 26, 535897),
+    datetime.datetime(2021, 3, 14, 1, 59, 26, 535897,
+                      tzinfo=datetime.timezone(datetime.timedelta(hours=3))),
+    datetime.datetime(1969, 7, 20, 20, 17, 40,
+                      tzinfo=date_ex.FixedOffset(-3600 * 4)),
+])
+def test_to_utc(d):
+    # Dates and naive datetimes are in UTC, not local time
+    aware = d
+    if type(aware) is datetime.date:
+        aware = datetime.datetime.combine(aware, datetime.time())
+    if aware.tzinfo is None:
+        aware = aware.replace(tzinfo=datetime.timezone.utc)
+    assert date_ex.to_utc(d) == aware.timestamp()
+
+
+@pytest.mark.parametrize('tz', [
+    None,
+    datetime.timezone.utc,
+    date_ex.FixedOffset(19800),
+    date_ex.FixedOffset(-3600 * 11),
+])
+@pytest.mark.parametrize('ts', [0, -1.5, 1e9 + 0.25, -62135596800, 253402300799])
+def test_from_utc(ts, tz):
+    utc = (datetime.datetime(1970, 1, 1, tzinfo=datetime.timezone.utc)
+           + datetime.timedelta(seconds=ts))
+    if tz is None:
+        expected = utc.replace(tzinfo=None)
+    else:
+        try:
+            expected = utc.astimezone(datetime.timezone(tz.utcoffset(None)))
+        except OverflowError:
+            with pytest.raises(OverflowError):
+                date_ex.from_utc(ts, tz)
+            return
+
+    result = date_ex.from_utc(ts, tz)
+    assert result.tzinfo is tz
+    assert result.replace(tzinfo=None) == expected.replace(tzinfo=None)
+    assert result.utcoffset() == expected.utcoffset()
+    assert date_ex.to_utc(result) == ts
+
+
+@pytest.mark.parametrize('ts', [-62135596801, 253402300800, 1e300, float('inf')])
+def test_from_utc_out_of_range(ts):
+    with pytest.raises(OverflowError):
+        date_ex.from_utc(ts)
+
+
+def test_from_utc_nan():
+    with pytest.raises(ValueError):
+        date_ex.from_utc(float('nan'))
+
+
+def zone(key):
+    return date_ex.ZoneInfo(key, ZONEINFO_DIR)
+
+
+def offset_hours(dt):
+    return dt.utcoffset() / datetime.timedelta(hours=1)
+
+
+def test_zoneinfo_is_tzinfo():
+    tz = zone('America/New_York')
+    assert isinstance(tz, datetime.tzinfo)
+    assert tz.key == 'America/New_York'
+    assert tz.utcoffset(None) is None
+    assert tz.tzname(None) is None
+
+
+@pytest.mark.parametrize('dt,hours,dst_hours,name', [
+    (datetime.datetime(2021, 1, 15, 12), -5, 0, 'EST'),
+    (datetime.datetime(2021, 7, 15, 12), -4, 1, 'EDT'),
+    (datetime.datetime(1944, 6, 1), -4, 1, 'EWT'),
+    # After the last transition in the file, the POSIX TZ footer applies
+    (datetime.datetime(2100, 1, 1), -5, 0, 'EST'),
+    (datetime.datetime(2100, 7, 1), -4, 1, 'EDT'),
+    (datetime.datetime(9999, 12, 31, 23), -5, 0, 'EST'),
+])
+def test_zoneinfo_new_york(dt, hours, dst_hours, name):
+    dt = dt.replace(tzinfo=zone('America/New_York'))
+    assert offset_hours(dt) == hours
+    assert dt.dst() == datetime.timedelta(hours=dst_hours)
+    assert dt.tzname() == name
+
+
+def test_zoneinfo_local_mean_time():
+    dt = datetime.datetime(1883, 11, 18, 12, tzinfo=zone('America/New_York'))
+    assert dt.utcoffset() == -datetime.timedelta(hours=4, minutes=56, seconds=2)
+    assert dt.tzname() == 'LMT'
+
+
+def test_zoneinfo_fold():
+    # 01:30 happens twice when clocks go back at 02:00 EDT
+    tz = zone('America/New_York')
+    first = datetime.datetime(2021, 11, 7, 1, 30, tzinfo=tz)
+    second = first.replace(fold=1)
+    assert (first.tzname(), second.tzname()) == ('EDT', 'EST')
+    assert second.timestamp() - first.timestamp() == 3600
+
+    utc = datetime.datetime(2021, 11, 7, 5, 30, tzinfo=datetime.timezone.utc)
+    assert utc.astimezone(tz).fold == 0
+    assert (utc + datetime.timedelta(hours=1)).astimezone(tz).fold == 1
+    assert (utc + datetime.timedelta(hours=2)).astimezone(tz).fold == 0
+
+
+def test_zoneinfo_gap():
+    # 02:30 never happens when clocks go forward at 02:00 EST, and the
+    # offsets are those before and after the change
+    tz = zone('America/New_York')
+    dt = datetime.datetime(2021, 3, 14, 2, 30, tzinfo=tz)
+    assert offset_hours(dt) == -5
+    assert offset_hours(dt.replace(fold=1)) == -4
+
+    utc = datetime.datetime(2021, 3, 14, 7, 0, tzinfo=datetime.timezone.utc)
+    local = utc.astimezone(tz)
+    assert local.replace(tzinfo=None) == datetime.datetime(2021, 3, 14, 3, 0)
+    assert local.fold == 0
+
+
+def test_zoneinfo_footer_fold():
+    tz = zone('America/New_York')
+    first = datetime.datetime(2200, 11, 2, 1, 30, tzinfo=tz)
+    assert (first.tzname(), first.replace(fold=1).tzname()) == ('EDT', 'EST')
+
+    utc = datetime.datetime(2200, 11, 2, 6, 30, tzinfo=datetime.timezone.utc)
+    local = utc.astimezone(tz)
+    assert local.replace(tzinfo=None) == datetime.datetime(2200, 11, 2, 1, 30)
+    assert local.fold == 1
+
+
+def test_zoneinfo_fromutc_other_tzinfo():
+    # Like tzinfo.fromutc, dt must already have been given this tzinfo
+    tz = zone('America/New_York')
+    for dt in [
+        datetime.datetime(2021, 11, 7, 6, 30),
+        datetime.datetime(2021, 11, 7, 6, 30, tzinfo=zone('Europe/Dublin')),
+        datetime.datetime(2021, 11, 7, 6, 30, tzinfo=datetime.timezone.utc),
+    ]:
+        with pytest.raises(ValueError, match='is not self'):
+            tz.fromutc(dt)
+
+    local = tz.fromutc(datetime.datetime(2021, 11, 7, 6, 30, tzinfo=tz))
+    assert local.tzinfo is tz
+    assert local.replace(tzinfo=None) == datetime.datetime(2021, 11, 7, 1, 30)
+    assert local.fold == 1
+
+
+@pytest.mark.parametrize('key,dt,hours,dst_hours,name', [
+    # Southern hemisphere, with DST over the new year
+    ('Australia/Sydney', datetime.datetime(2030, 1, 1), 11, 1, 'AEDT'),
+    ('Australia/Sydney', datetime.datetime(2030, 7, 1), 10, 0, 'AEST'),
+    # Negative DST, with the winter time the daylight saving one
+    ('Europe/Dublin', datetime.datetime(2030, 1, 1), 0, -1, 'GMT'),
+    ('Europe/Dublin', datetime.datetime(2030, 7, 1), 1, 0, 'IST'),
+    # DST starting at -01:00, i.e. 23:00 on the Saturday before
+    ('America/Nuuk', datetime.datetime(2030, 3, 30, 22, 59), -2, 0, '-02'),
+    ('America/Nuuk', datetime.datetime(2030, 3, 31, 0, 0), -1, 1, '-01'),
+    ('Asia/Kolkata', datetime.datetime(2030, 1, 1), 5.5, 0, 'IST'),
+    ('UTC', datetime.datetime(2030, 1, 1), 0, 0, 'UTC'),
+])
+def test_zoneinfo_zones(key, dt, hours, dst_hours, name):
+    dt = dt.replace(tzinfo=zone(key))
+    assert offset_hours(dt) == hours
+    assert dt.dst() == datetime.timedelta(hours=dst_hours)
+    assert dt.tzname() == name
+
+
+def test_zoneinfo_fixed_zone():
+    tz = zone('UTC')
+    assert tz.utcoffset(None) == datetime.timedelta(0)
+    assert tz.tzname(None) == 'UTC'
+
+
+def test_zoneinfo_version_1():
+    # A file with no 64-bit data or footer keeps its last offset for ever
+    tz = zone('v1_New_York')
+    assert offset_hours(datetime.datetime(2021, 7, 1, tzinfo=tz)) == -4
+    assert offset_hours(datetime.datetime(2100, 7, 1, tzinfo=tz)) == -5
+
+
+@pytest.mark.parametrize('key', [
+    'America/New_York', 'Australia/Sydney', 'Europe/Dublin', 'America/Nuuk',
+    'Asia/Kolkata', 'UTC', 'v1_New_York',
+])
+def test_zoneinfo_matches_stdlib(key):
+    zoneinfo = pytest.importorskip('zoneinfo')
+    with open(os.path.join(ZONEINFO_DIR, key), 'rb') as f:
+        expected_tz = zoneinfo.ZoneInfo.from_file(f)
+    tz = zone(key)
+
+    epoch = datetime.datetime(1970, 1, 1, tzinfo=datetime.timezone.utc)
+    hour = datetime.timedelta(hours=1)
+    for hours in range(-24 * 366 * 130, 24 * 366 * 130, 101):
+        utc = epoch + hours * hour
+        local = utc.astimezone(tz)
+        expected = utc.astimezone(expected_tz)
+        assert local.replace(tzinfo=None) == expected.replace(tzinfo=None), utc
+        assert local.fold == expected.fold, utc
+
+        for fold in (0, 1):
+            wall = utc.replace(tzinfo=None, fold=fold)
+            assert (wall.replace(tzinfo=tz).utcoffset()
+                    == wall.replace(tzinfo=expected_tz).utcoffset()), wall
+            assert (wall.replace(tzinfo=tz).tzname()
+                    == wall.replace(tzinfo=expected_tz).tzname()), wall
+
+
+@pytest.mark.parametrize('key', ['../UTC', '/etc/passwd', 'America/../UTC', ''])
+def test_zoneinfo_invalid_key(key):
+    with pytest.raises(ValueError):
+        zone(key)
+
+
+def test_zoneinfo_missing_key():
+    with pytest.raises(KeyError):
+        zone('Mars/Olympus_Mons')
+
+
+def test_zoneinfo_not_tzif():
+    with pytest.raises(ValueError, match='TZif'):
+        date_ex.ZoneInfo('test_date_ex.py', os.path.dirname(__file__))
@@ -0,0 +1,77 @@
+import math
+import re
+
+import pytest
+
+from pomodule import backend
+
+
+@pytest.mark.parametrize('kwargs', [
+    {},
+    {'dtype': 'u64'},
+    {'dtype': 'u128'},
+    {'exact': True},
+])
+def test_pascal_row_zero(kwargs):
+    assert backend.pascal_row(0, **kwargs) == []
+
+
+@pytest.mark.parametrize('kwargs', [
+    {},
+    {'dtype': 'u64'},
+    {'dtype': 'u128'},
+    {'exact': True},
+])
+def test_pascal_row_one(kwargs):
+    assert backend.pascal_row(1, **kwargs) == [1]
+
+
+@pytest.mark.parametrize('n', [1000, 1001, 2048, 4097])
+def test_pascal_row_exact(n):
+    # Entries span many limbs, and rows of both even and odd length check
+    # that the second half is mirrored from the right entries
+    expected = [math.comb(n - 1, k) for k in range(n)]
+    assert backend.pascal_row(n, exact=True) == expected
+
+
+@pytest.mark.parametrize('dtype,longest,entry', [
+    ('u32', 35, 'C(35, 17)'),
+    ('u64', 68, 'C(68, 31)'),
+    ('u128', 132, 'C(132, 64)'),
+])
+def test_pascal_row_dtype_limit(dtype, longest, entry):
+    # The message names the first entry of row longest + 1 that overflows
+    row = backend.pascal_row(longest, dtype=dtype)
+    assert row == [math.comb(longest - 1, k) for k in range(longest)]
+
+    message = re.escape('{} does not fit in {}'.format(entry, dtype))
+    with pytest.raises(OverflowError, match=message):
+        backend.pascal_row(longest + 1, dtype=dtype)
+
+
+def test_pascal_row_mod_zero():
+    assert backend.pascal_row_mod(0, 7) == []
+
+
+def test_pascal_row_buffer_zero():
+    assert memoryview(backend.pascal_row_buffer(0)).tolist() == []
+
+
+@pytest.mark.parametrize('dtype', ['u32', 'u64', 'u128'])
+def test_pascal_row_huge(dtype):
+    # Must overflow quickly rather than trying to allocate 2**62 entries
+    with pytest.raises(OverflowError):
+        backend.pascal_row(2 ** 62, dtype=dtype)
+
+
+@pytest.mark.parametrize('n', [2 ** 62, 2 ** 64 - 1])
+def test_pascal_row_huge_exact(n):
+    # Entries never overflow, but there are too many of them to allocate
+    with pytest.raises(MemoryError):
+        backend.pascal_row(n, exact=True)
+
+
+@pytest.mark.parametrize('n', [2 ** 62, 2 ** 64 - 1])
+def test_pascal_row_mod_huge(n):
+    with pytest.raises(MemoryError):
+        backend.pascal_row_mod(n, 7)
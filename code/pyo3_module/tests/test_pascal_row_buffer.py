@@ -0,0 +1,74 @@
+import array
+import gc
+
+import pytest
+
+from pomodule import backend
+
+
+@pytest.mark.parametrize('dtype,format', [('u32', 'I'), ('u64', 'Q')])
+@pytest.mark.parametrize('n', [1, 2, 10, 35])
+def test_memoryview(n, dtype, format):
+    expected = backend.pascal_row(n, dtype=dtype)
+    view = memoryview(backend.pascal_row_buffer(n, dtype))
+
+    assert view.format == format
+    assert view.itemsize == array.array(format).itemsize
+    assert view.ndim == 1
+    assert view.shape == (n,)
+    assert view.strides == (view.itemsize,)
+    assert view.readonly
+    assert view.tolist() == expected
+    assert view.tobytes() == array.array(format, expected).tobytes()
+
+
+def test_default_dtype_is_u32():
+    assert memoryview(backend.pascal_row_buffer(5)).format == 'I'
+
+
+def test_u64_row_beyond_u32():
+    view = memoryview(backend.pascal_row_buffer(60, 'u64'))
+    assert view.tolist() == backend.pascal_row(60, dtype='u64')
+
+
+def test_overflow():
+    with pytest.raises(OverflowError):
+        backend.pascal_row_buffer(36)
+
+
+def test_read_only():
+    view = memoryview(backend.pascal_row_buffer(5))
+    with pytest.raises(TypeError):
+        view[0] = 2
+
+
+def test_view_keeps_buffer_alive():
+    buffer = backend.pascal_row_buffer(30, 'u64')
+    view = memoryview(buffer)
+    assert view.obj is buffer
+
+    del buffer
+    gc.collect()
+    # Allocate enough to reuse the row's memory if it had been freed
+    [backend.pascal_row_buffer(30, 'u64') for _ in range(100)]
+    assert view.tolist() == backend.pascal_row(30, dtype='u64')
+
+
+@pytest.mark.parametrize('dtype', ['u128', 'exact'])
+def test_unsupported_dtype(dtype):
+    with pytest.raises(ValueError):
+        backend.pascal_row_buffer(5, dtype)
+
+
+@pytest.mark.parametrize('dtype,numpy_dtype', [
+    ('u32', 'uint32'),
+    ('u64', 'uint64'),
+])
+def test_numpy(dtype, numpy_dtype):
+    numpy = pytest.importorskip('numpy')
+    buffer = backend.pascal_row_buffer(30, dtype)
+    expected = backend.pascal_row(30, dtype=dtype)
+
+    values = numpy.frombuffer(buffer, dtype=numpy_dtype)
+    assert values.tolist() == expected
+    assert numpy.asarray(buffer).dtype == numpy.dtype(numpy_dtype)
@@ -0,0 +1,103 @@
+import itertools
+
+import pytest
+
+from pomodule import backend
+
+
+@pytest.mark.parametrize('kwargs,count', [
+    ({}, 35),
+    ({'dtype': 'u64'}, 68),
+    ({'dtype': 'u128'}, 132),
+    ({'exact': True}, 300),
+])
+def test_iteration_matches_pascal_row(kwargs, count):
+    triangle = backend.PascalTriangle(**kwargs)
+    assert triangle.current_index == 0
+
+    rows = list(itertools.islice(triangle, count))
+    expected = [backend.pascal_row(n, **kwargs) for n in range(1, count + 1)]
+    assert rows == expected
+    assert triangle.current_index == count
+
+
+def test_iter_returns_self():
+    triangle = backend.PascalTriangle()
+    assert iter(triangle) is triangle
+
+
+def test_skip():
+    triangle = backend.PascalTriangle(exact=True)
+    triangle.skip(0)
+    assert triangle.current_index == 0
+
+    triangle.skip(99)
+    assert triangle.current_index == 99
+    assert next(triangle) == backend.pascal_row(100, exact=True)
+
+    triangle.skip(10)
+    assert triangle.current_index == 110
+    assert next(triangle) == backend.pascal_row(111, exact=True)
+
+
+def test_seek():
+    triangle = backend.PascalTriangle(dtype='u64')
+    triangle.seek(20)
+    assert triangle.current_index == 20
+    assert next(triangle) == backend.pascal_row(21)
+
+    # Backwards, which restarts from the top
+    triangle.seek(5)
+    assert triangle.current_index == 5
+    assert next(triangle) == backend.pascal_row(6)
+
+    triangle.seek(6)
+    assert triangle.current_index == 6
+    assert next(triangle) == backend.pascal_row(7)
+
+    triangle.seek(0)
+    assert next(triangle) == [1]
+
+
+def test_next_overflow():
+    triangle = backend.PascalTriangle()
+    triangle.seek(35)
+
+    # The current row is kept, so the error is raised again
+    for _ in range(2):
+        with pytest.raises(OverflowError, match=r'C\(35, 17\)'):
+            next(triangle)
+        assert triangle.current_index == 35
+
+
+@pytest.mark.parametrize('method', ['skip', 'seek'])
+def test_skip_and_seek_overflow(method):
+    triangle = backend.PascalTriangle()
+    with pytest.raises(OverflowError):
+        getattr(triangle, method)(100)
+
+    # Every row that fits has been reached
+    assert triangle.current_index == 35
+
+
+@pytest.mark.parametrize('kwargs,longest', [
+    ({}, 35),
+    ({'dtype': 'u64'}, 68),
+    ({'dtype': 'u128'}, 132),
+    ({'exact': True}, 300),
+])
+def test_pascal_triangle(kwargs, longest):
+    for n in [0, 1, 2, longest]:
+        expected = [backend.pascal_row(i, **kwargs) for i in range(1, n + 1)]
+        assert backend.pascal_triangle(n, **kwargs) == expected
+
+
+@pytest.mark.parametrize('dtype,longest,entry', [
+    ('u32', 35, r'C\(35, 17\)'),
+    ('u64', 68, r'C\(68, 31\)'),
+    ('u128', 132, r'C\(132, 64\)'),
+])
+def test_pascal_triangle_overflow(dtype, longest, entry):
+    for n in [longest + 1, 10 ** 6]:
+        with pytest.raises(OverflowError, match=entry):
+            backend.pascal_triangle(n, dtype=dtype)
@@ -0,0 +1,33 @@
+import _thread
+import threading
+import time
+
+import pytest
+
+from pomodule import backend
+
+
+def _interrupt_after(delay):
+    """Raise KeyboardInterrupt in the main thread after ``delay`` seconds"""
+    timer = threading.Timer(delay, _thread.interrupt_main)
+    timer.start()
+    return timer
+
+
+@pytest.mark.parametrize('func,args', [
+    (backend.pascal_row, (200000, True)),
+    (backend.binomial, (2000000, 1000000, True)),
+    # binomial_mod is too quick to interrupt reliably, but a row this long
+    # modulo a large prime takes seconds
+    (backend.pascal_row_mod, (3 * 10 ** 7, 1000000007)),
+])
+def test_interrupt(func, args):
+    timer = _interrupt_after(0.1)
+    start = time.perf_counter()
+    try:
+        with pytest.raises(KeyboardInterrupt):
+            func(*args)
+    finally:
+        timer.cancel()
+
+    assert time.perf_counter() - start < 5
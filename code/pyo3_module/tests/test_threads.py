@@ -0,0 +1,65 @@
+import threading
+import time
+
+from pomodule import backend
+
+
+def _longest_stall(func, *args):
+    """Run func in another thread, watching how long this thread is blocked
+
+    Returns the duration of the call and the longest gap between two
+    iterations of a sleep loop in the calling thread while the call runs. If
+    the GIL were held for the whole call, the gap would be about as long as
+    the call itself.
+    """
+    started = threading.Event()
+    durations = []
+
+    def worker():
+        started.set()
+        start = time.perf_counter()
+        func(*args)
+        durations.append(time.perf_counter() - start)
+
+    thread = threading.Thread(target=worker)
+    thread.start()
+    started.wait()
+
+    longest = 0.0
+    last = time.perf_counter()
+    while thread.is_alive():
+        time.sleep(0.001)
+        now = time.perf_counter()
+        longest = max(longest, now - last)
+        last = now
+
+    thread.join()
+    return durations[0], longest
+
+
+def test_pascal_row_releases_gil():
+    duration, stall = _longest_stall(backend.pascal_row, 40000, True)
+
+    assert stall < duration / 2
+
+
+def test_pascal_triangle_releases_gil():
+    # Converting the rows to lists needs the GIL, but is done a chunk at a time
+    duration, stall = _longest_stall(backend.pascal_triangle, 1500, True)
+
+    assert stall < duration / 2
+
+
+def test_binomial_releases_gil():
+    duration, stall = _longest_stall(backend.binomial, 200000, 100000, True)
+
+    assert stall < duration / 2
+
+
+def test_binomial_mod_releases_gil():
+    # 1000000007 is too large to tabulate in full, so a digit of k this large
+    # first needs the worker thread to build its own FactorialTable
+    duration, stall = _longest_stall(backend.binomial_mod, 10 ** 9, 2 * 10 ** 7,
+                                     1000000007)
+
+    assert stall < duration / 2